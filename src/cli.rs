@@ -1,4 +1,4 @@
-use clap::{command, Parser};
+use clap::Parser;
 
 use crate::{
   dry_mode::set_dry_mode,
@@ -14,12 +14,12 @@ pub struct Cli {
 
   /// Do not actually run the task, just print what would be done
   #[arg(short = 'n', long)]
-  dry_run: bool,
+  pub(crate) dry_run: bool,
 }
 
 impl YmspTask for Cli {
   fn run(&self) -> color_eyre::Result<()> {
-    set_dry_mode(self.dry_run)?;
+    set_dry_mode(self.dry_run);
 
     self.task.run()
   }
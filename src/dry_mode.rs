@@ -1,9 +1,5 @@
-use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use color_eyre::eyre::eyre;
-
-static DRY_MODE: OnceLock<bool> = OnceLock::new();
-pub(crate) fn is_dry_mode() -> bool { DRY_MODE.get().is_some_and(|f| *f) }
-pub(crate) fn set_dry_mode(value: bool) -> color_eyre::Result<()> {
-  DRY_MODE.set(value).map_err(|_| eyre!("Failed to set dry mode"))
-}
+static DRY_MODE: AtomicBool = AtomicBool::new(false);
+pub(crate) fn is_dry_mode() -> bool { DRY_MODE.load(Ordering::SeqCst) }
+pub(crate) fn set_dry_mode(value: bool) { DRY_MODE.store(value, Ordering::SeqCst) }
@@ -1,4 +1,4 @@
-#![feature(once_cell_try)]
+#![cfg_attr(not(test), feature(once_cell_try))]
 #![cfg_attr(debug_assertions, allow(dead_code, unused_variables))]
 
 pub mod cli;
@@ -7,6 +7,8 @@ mod initialize_logging;
 mod initialize_panic_handler;
 mod macros;
 mod task;
+#[cfg(test)]
+mod test_dir;
 mod trace_command;
 pub mod window_manager;
 mod yabai;
@@ -14,17 +14,29 @@ pub struct InitializedWindowsManager {
 }
 
 pub(super) fn create_initialized_windows_manager() -> color_eyre::Result<InitializedWindowsManager> {
-  trace!("Initializing windows manager");
-  let mut state = State::read_state()?;
-  let display = YabaiMessage::query().current_display()?;
-  trace!("Focused display: {:?}", display);
   let space = YabaiMessage::query().current_space()?;
   trace!("Focused space: {:?}", space);
+  create_initialized_windows_manager_for(space)
+}
+
+/// Initializes a windows manager for `space`, which doesn't have to be the focused space.
+pub(super) fn create_initialized_windows_manager_for(space: Space) -> color_eyre::Result<InitializedWindowsManager> {
+  trace!("Initializing windows manager");
+  let state = State::read_state()?;
+  let display = YabaiMessage::query().display(space.display)?;
+  trace!("Display: {:?}", display);
   let space_state = state.get_space(&space)?;
   trace!("Space state: {} for {}", space_state.blue(), space.id.blue());
-  let mut wm = WindowsManager::new(display.clone(), space.clone(), *space_state);
+  let master_ratio = state.get_ratio(&space);
+  trace!("Master ratio: {master_ratio:?} for {}", space.id.blue());
+  let layout = state.get_layout(&space);
+  trace!("Layout: {layout} for {}", space.id.blue());
+  let window_order = state.get_window_order(&space);
+  trace!("Window order: {window_order:?} for {}", space.id.blue());
+  let mut wm = WindowsManager::new(display.clone(), space.clone(), *space_state, master_ratio, layout, window_order);
+  wm.focus_history = state.get_focus_history(&space).to_vec();
+  wm.insertion_point = state.get_insertion_point(&space);
   wm.initialize()?;
-  wm.validate_state(&mut state)?;
 
   Ok(InitializedWindowsManager { wm, state, display, space })
 }
@@ -0,0 +1,213 @@
+use std::{
+  io::{BufRead, BufReader, ErrorKind, Write},
+  os::unix::net::{UnixListener, UnixStream},
+  path::Path,
+  sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::Parser as _;
+use color_eyre::{eyre::bail, owo_colors::OwoColorize};
+use log::{debug, error, info, trace};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  cli::Cli,
+  dry_mode::is_dry_mode,
+  task::{
+    debounce::{get_debounce_delay, run_debounced_event},
+    event_queue::Event,
+    handlers::completion::generate_completion,
+    lock::run_event,
+    ymsp_task::YmspTask,
+    Task,
+  },
+  yabai::config::get_socket_path,
+};
+
+static IS_DAEMON: AtomicBool = AtomicBool::new(false);
+
+/// Whether the current process is the daemon, which keeps the state file in memory until another process changes it.
+pub(crate) fn is_daemon() -> bool { IS_DAEMON.load(Ordering::SeqCst) }
+
+/// A task forwarded by `ymsp send` to the daemon.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+enum Request {
+  /// A yabai signal, with the ids yabai passed to the client
+  Event { event: Event },
+  /// Any other task, as command line arguments
+  Task { args: Vec<String> },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Response {
+  error: Option<String>,
+}
+
+fn handle_request(request: Request) -> color_eyre::Result<()> {
+  match request {
+    // Debouncing waits for the rest of the burst, which must keep reaching the daemon meanwhile
+    Request::Event { event } if get_debounce_delay(&event)?.is_some() => {
+      std::thread::spawn(move || {
+        if let Err(err) = run_debounced_event(event) {
+          error!("Unable to handle debounced event: {err}");
+        }
+      });
+      Ok(())
+    },
+    Request::Event { event } => run_event(event),
+    Request::Task { args } => {
+      let cli = Cli::try_parse_from(std::iter::once("ymsp".to_string()).chain(args))?;
+      if !cli.task.is_forwardable() {
+        bail!("{} can't be run by the daemon", cli.task.yellow());
+      }
+      // The dry mode is shared with the debounced events running meanwhile, `ymsp send` keeps dry runs local instead
+      if cli.dry_run && !is_dry_mode() {
+        bail!("Dry runs of {} can't be run by the daemon", cli.task.yellow());
+      }
+      cli.task.run()
+    },
+  }
+}
+
+fn handle_connection(stream: UnixStream) -> color_eyre::Result<()> {
+  let mut line = String::new();
+  BufReader::new(&stream).read_line(&mut line)?;
+  let request: Request = serde_json::from_str(&line)?;
+  debug!("Received {request:?}");
+
+  let response = match handle_request(request) {
+    Ok(()) => Response::default(),
+    Err(err) => {
+      error!("Unable to handle request: {err}");
+      Response { error: Some(err.to_string()) }
+    },
+  };
+  let mut json = serde_json::to_string(&response)?;
+  json.push('\n');
+  (&stream).write_all(json.as_bytes())?;
+
+  Ok(())
+}
+
+/// Binds the daemon socket, replacing the socket left by a daemon that is no longer running.
+fn bind_socket(path: &Path) -> color_eyre::Result<UnixListener> {
+  if path.exists() {
+    if UnixStream::connect(path).is_ok() {
+      bail!("A daemon is already listening on {path:?}");
+    }
+    debug!("Removing stale socket {path:?}");
+    std::fs::remove_file(path)?;
+  }
+
+  Ok(UnixListener::bind(path)?)
+}
+
+/// Runs the tasks sent by `ymsp send`, one at a time, until the process is killed.
+///
+/// The config and the state are loaded once, windows and spaces are still queried from yabai for every task: they
+/// change between signals, and while a task waits for yabai to apply its own changes.
+pub(crate) fn run_daemon() -> color_eyre::Result<()> {
+  let path = get_socket_path()?;
+  let listener = bind_socket(&path)?;
+  IS_DAEMON.store(true, Ordering::SeqCst);
+  info!("Daemon listening on {path:?}", path = path.yellow());
+
+  for stream in listener.incoming() {
+    // A failing client must not stop the daemon
+    if let Err(err) = stream.map_err(Into::into).and_then(handle_connection) {
+      error!("Unable to handle connection: {err}");
+    }
+  }
+
+  Ok(())
+}
+
+/// Forwards the request to the daemon, or returns `None` when no daemon is listening.
+fn forward(path: &Path, request: &Request) -> color_eyre::Result<Option<Response>> {
+  let mut stream = match UnixStream::connect(path) {
+    Ok(stream) => stream,
+    Err(err) if matches!(err.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => return Ok(None),
+    Err(err) => return Err(err.into()),
+  };
+  let mut json = serde_json::to_string(request)?;
+  json.push('\n');
+  stream.write_all(json.as_bytes())?;
+
+  let mut line = String::new();
+  BufReader::new(&stream).read_line(&mut line)?;
+  Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Runs `args` in the daemon, or in the current process when no daemon is running.
+pub(crate) fn send(args: &[String]) -> color_eyre::Result<()> {
+  let mut cli = Cli::try_parse_from(std::iter::once("ymsp".to_string()).chain(args.iter().cloned()))?;
+  if let Task::Completions(completion) = &cli.task {
+    return generate_completion(completion);
+  }
+  // Both `ymsp -n send task` and `ymsp send -n task` are dry runs
+  cli.dry_run |= is_dry_mode();
+  // Tasks printing to the terminal, and dry runs, stay in the current process
+  if !cli.task.is_forwardable() || cli.dry_run {
+    trace!("Running {} in the current process", cli.task.yellow());
+    return cli.run();
+  }
+
+  let request = match cli.task.to_event()? {
+    Some(event) => Request::Event { event },
+    None => Request::Task { args: args.to_vec() },
+  };
+  match forward(&get_socket_path()?, &request)? {
+    Some(Response { error: None }) => Ok(()),
+    Some(Response { error: Some(error) }) => {
+      bail!("Daemon failed to run {}: {error}", cli.task.yellow());
+    },
+    None => {
+      debug!("No daemon is running, running {} in the current process", cli.task.yellow());
+      cli.run()
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::test_dir::TestDir;
+
+  #[test_log::test]
+  fn forward_without_daemon_falls_back() {
+    let dir = TestDir::new("daemon-missing");
+    let path = dir.path("ymsp.sock");
+    let request = Request::Event { event: Event::WindowMoved { space: 1 } };
+    assert!(forward(&path, &request).unwrap().is_none());
+  }
+
+  #[test_log::test]
+  fn forward_returns_daemon_response() {
+    let dir = TestDir::new("daemon-forward");
+    let path = dir.path("ymsp.sock");
+    let listener = bind_socket(&path).unwrap();
+    let daemon = std::thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut line = String::new();
+      BufReader::new(&stream).read_line(&mut line).unwrap();
+      let request: Request = serde_json::from_str(&line).unwrap();
+      (&stream).write_all(b"{\"error\":\"boom\"}\n").unwrap();
+      request
+    });
+
+    let request = Request::Task { args: vec!["rotate-next".to_string()] };
+    let response = forward(&path, &request).unwrap().unwrap();
+    assert_eq!(response.error, Some("boom".to_string()));
+    assert!(matches!(daemon.join().unwrap(), Request::Task { args } if args == ["rotate-next"]));
+  }
+
+  #[test_log::test]
+  fn dry_run_request_is_refused() {
+    let request = Request::Task { args: vec!["-n".to_string(), "rotate-next".to_string()] };
+    assert!(handle_request(request).is_err());
+    assert!(!is_dry_mode());
+  }
+}
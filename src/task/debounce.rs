@@ -0,0 +1,149 @@
+use std::{
+  path::Path,
+  sync::atomic::{AtomicUsize, Ordering},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, trace};
+
+use crate::{
+  task::{event_queue::Event, lock::run_event},
+  yabai::config::{get_config, get_debounce_marker_path},
+};
+
+/// Number of markers armed by the process, telling apart the temporary files of the daemon threads.
+static ARMED_MARKERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Source of time of the debouncer, faked in tests.
+pub(crate) trait Clock {
+  /// The time elapsed since the Unix epoch.
+  fn now(&self) -> Duration;
+  fn sleep(&self, duration: Duration);
+}
+
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> Duration { SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default() }
+
+  fn sleep(&self, duration: Duration) { std::thread::sleep(duration) }
+}
+
+/// Marks the event as the latest of its burst, returning the token identifying it in the marker at `path`.
+fn arm_marker(path: &Path, clock: &impl Clock, delay: Duration) -> color_eyre::Result<String> {
+  let deadline = clock.now() + delay;
+  let token = format!("{pid} {deadline}", pid = std::process::id(), deadline = deadline.as_nanos());
+  // Written aside then renamed, so a process reading the marker never sees half a token
+  let count = ARMED_MARKERS.fetch_add(1, Ordering::SeqCst);
+  let temp_path = path.with_extension(format!("{pid}.{count}.tmp", pid = std::process::id()));
+  std::fs::write(&temp_path, &token)?;
+  std::fs::rename(&temp_path, path)?;
+
+  Ok(token)
+}
+
+/// Whether no event of the same burst arrived after the one holding `token`.
+fn is_latest(path: &Path, token: &str) -> bool { std::fs::read_to_string(path).is_ok_and(|marker| marker == token) }
+
+/// Waits `delay`, then tells whether the event should be handled, which is only the case for the last event of a burst.
+pub(crate) fn debounce_at(path: &Path, clock: &impl Clock, delay: Duration) -> color_eyre::Result<bool> {
+  let token = arm_marker(path, clock, delay)?;
+  clock.sleep(delay);
+  if !is_latest(path, &token) {
+    trace!("A later event superseded {token}");
+    return Ok(false);
+  }
+  // The marker is left for the next burst to overwrite: removing it could delete the one armed by a later event
+
+  Ok(true)
+}
+
+/// The debounce delay configured for the kind of `event`.
+pub(crate) fn get_debounce_delay(event: &Event) -> color_eyre::Result<Option<Duration>> {
+  let config = get_config()?;
+  let delay = config.event_debounce_ms.get(event.get_name()).copied().filter(|delay| *delay > 0);
+
+  Ok(delay.map(Duration::from_millis))
+}
+
+/// Handles `event` unless another event of the same kind on the same space or window arrives within its debounce
+/// delay.
+pub(crate) fn run_debounced_event(event: Event) -> color_eyre::Result<()> {
+  let Some(delay) = get_debounce_delay(&event)? else {
+    return run_event(event);
+  };
+
+  let path = get_debounce_marker_path(&event.get_debounce_key())?;
+  if debounce_at(&path, &SystemClock, delay)? {
+    run_event(event)
+  } else {
+    debug!("Dropping {event:?}, a later event of its burst is handled instead");
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::cell::Cell;
+
+  use super::*;
+  use crate::test_dir::TestDir;
+
+  #[derive(Default)]
+  struct FakeClock {
+    now: Cell<Duration>,
+  }
+
+  impl FakeClock {
+    fn advance(&self, duration: Duration) { self.now.set(self.now.get() + duration) }
+  }
+
+  impl Clock for FakeClock {
+    fn now(&self) -> Duration { self.now.get() }
+
+    fn sleep(&self, duration: Duration) { self.advance(duration) }
+  }
+
+  #[test_log::test]
+  fn single_event_is_handled() {
+    let dir = TestDir::new("debounce-single");
+    let path = dir.path("event.marker");
+    let clock = FakeClock::default();
+
+    assert!(debounce_at(&path, &clock, Duration::from_millis(150)).unwrap());
+    assert_eq!(clock.now(), Duration::from_millis(150));
+    // Left in place, it may already belong to a later event
+    assert!(path.exists());
+  }
+
+  #[test_log::test]
+  fn only_last_event_of_burst_is_handled() {
+    let dir = TestDir::new("debounce-burst");
+    let path = dir.path("event.marker");
+    let clock = FakeClock::default();
+    let delay = Duration::from_millis(150);
+
+    let first = arm_marker(&path, &clock, delay).unwrap();
+    clock.advance(Duration::from_millis(20));
+    let second = arm_marker(&path, &clock, delay).unwrap();
+    clock.advance(Duration::from_millis(20));
+    let third = arm_marker(&path, &clock, delay).unwrap();
+
+    clock.advance(delay);
+    assert!(!is_latest(&path, &first));
+    assert!(!is_latest(&path, &second));
+    assert!(is_latest(&path, &third));
+  }
+
+  #[test_log::test]
+  fn event_after_burst_starts_new_burst() {
+    let dir = TestDir::new("debounce-after");
+    let path = dir.path("event.marker");
+    let clock = FakeClock::default();
+    let delay = Duration::from_millis(150);
+
+    assert!(debounce_at(&path, &clock, delay).unwrap());
+    clock.advance(Duration::from_secs(1));
+    assert!(debounce_at(&path, &clock, delay).unwrap());
+  }
+}
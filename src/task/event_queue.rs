@@ -0,0 +1,255 @@
+use std::{
+  collections::HashSet,
+  io::Write,
+  path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize};
+use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::yabai::command::message::YabaiMessage;
+
+/// A yabai signal, queued while another ymsp process holds the lock.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Event {
+  YabaiStarted,
+  /// `space` is the index of the space of the window when the event was queued, the focus may have moved since
+  WindowCreated {
+    process_id: usize,
+    window_id: usize,
+    space: usize,
+  },
+  WindowMoved {
+    space: usize,
+  },
+  WindowFocused {
+    window_id: usize,
+  },
+  WindowDestroyed {
+    window_id: usize,
+  },
+  WindowMinimized {
+    window_id: usize,
+  },
+  WindowDeminimized {
+    window_id: usize,
+  },
+  WindowResized {
+    window_id: usize,
+  },
+  ApplicationHidden {
+    process_id: usize,
+  },
+  ApplicationVisible {
+    process_id: usize,
+  },
+  SpaceChanged {
+    space_id: usize,
+  },
+  DisplayAdded {
+    display_id: usize,
+  },
+  DisplayRemoved {
+    display_id: usize,
+  },
+  DisplayChanged {
+    display_id: usize,
+  },
+}
+
+impl Event {
+  /// The name of the kind of event, as used in the configuration.
+  pub(crate) fn get_name(&self) -> &'static str {
+    match self {
+      Event::YabaiStarted => "yabai_started",
+      Event::WindowCreated { .. } => "window_created",
+      Event::WindowMoved { .. } => "window_moved",
+      Event::WindowFocused { .. } => "window_focused",
+      Event::WindowDestroyed { .. } => "window_destroyed",
+      Event::WindowMinimized { .. } => "window_minimized",
+      Event::WindowDeminimized { .. } => "window_deminimized",
+      Event::WindowResized { .. } => "window_resized",
+      Event::ApplicationHidden { .. } => "application_hidden",
+      Event::ApplicationVisible { .. } => "application_visible",
+      Event::SpaceChanged { .. } => "space_changed",
+      Event::DisplayAdded { .. } => "display_added",
+      Event::DisplayRemoved { .. } => "display_removed",
+      Event::DisplayChanged { .. } => "display_changed",
+    }
+  }
+
+  /// Identifies the bursts of the event: a burst on a space or window doesn't swallow the events of another one.
+  pub(crate) fn get_debounce_key(&self) -> String {
+    match (self, self.get_window_id()) {
+      (Event::WindowCreated { space, .. } | Event::WindowMoved { space }, _) => {
+        format!("{name}.space-{space}", name = self.get_name())
+      },
+      (_, Some(window_id)) => format!("{name}.window-{window_id}", name = self.get_name()),
+      (_, None) => self.get_name().to_string(),
+    }
+  }
+
+  fn get_window_id(&self) -> Option<usize> {
+    match self {
+      Event::WindowCreated { window_id, .. }
+      | Event::WindowFocused { window_id }
+      | Event::WindowDestroyed { window_id }
+      | Event::WindowMinimized { window_id }
+      | Event::WindowDeminimized { window_id }
+      | Event::WindowResized { window_id } => Some(*window_id),
+      _ => None,
+    }
+  }
+}
+
+/// Reads an id passed by yabai to the signal, e.g. `YABAI_WINDOW_ID`.
+pub(crate) fn get_yabai_env_id(name: &str) -> color_eyre::Result<usize> {
+  let value = std::env::var(name).map_err(|err| eyre!("Unable to read {name}: {err}"))?;
+  Ok(value.parse()?)
+}
+
+/// The index of the space of the window passed by yabai to the signal.
+pub(crate) fn get_yabai_window_space() -> color_eyre::Result<usize> {
+  let window_id = get_yabai_env_id("YABAI_WINDOW_ID")?;
+  Ok(YabaiMessage::query().window(window_id)?.space)
+}
+
+/// Appends `event` to the queue at `path`.
+pub(crate) fn queue_event(path: &Path, event: &Event) -> color_eyre::Result<()> {
+  let mut line = serde_json::to_string(event)?;
+  line.push('\n');
+  // A single append write, so lines of concurrent processes don't interleave
+  let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+  file.write_all(line.as_bytes())?;
+  trace!("Queued event {event:?}");
+
+  Ok(())
+}
+
+pub(crate) fn has_queued_events(path: &Path) -> bool {
+  std::fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0)
+}
+
+/// Takes every queued event out of the queue at `path`, in the order they were queued.
+pub(crate) fn drain_events(path: &Path) -> color_eyre::Result<Vec<Event>> {
+  // Events queued while draining go to a fresh queue instead of being lost
+  let draining_path = PathBuf::from(format!("{}.{}.draining", path.display(), std::process::id()));
+  match std::fs::rename(path, &draining_path) {
+    Ok(()) => {},
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+    Err(err) => return Err(err.into()),
+  }
+
+  let content = std::fs::read_to_string(&draining_path)?;
+  std::fs::remove_file(&draining_path)?;
+  let events = content
+    .lines()
+    .filter(|line| !line.trim().is_empty())
+    .filter_map(|line| {
+      match serde_json::from_str(line) {
+        Ok(event) => Some(event),
+        Err(err) => {
+          warn!("Skipping malformed queued event {}: {err}", line.red());
+          None
+        },
+      }
+    })
+    .collect::<Vec<Event>>();
+  debug!("Drained {len} events", len = events.len());
+
+  Ok(events)
+}
+
+/// Drops the events made redundant by later ones, so a burst of signals results in a single re-tile.
+///
+/// Identical events only keep their last occurrence, and a window created and destroyed within the batch is ignored.
+pub(crate) fn coalesce_events(events: Vec<Event>) -> Vec<Event> {
+  let created = events
+    .iter()
+    .filter(|event| matches!(event, Event::WindowCreated { .. }))
+    .filter_map(Event::get_window_id)
+    .collect::<HashSet<_>>();
+  let short_lived = events
+    .iter()
+    .filter(|event| matches!(event, Event::WindowDestroyed { .. }))
+    .filter_map(Event::get_window_id)
+    .filter(|window_id| created.contains(window_id))
+    .collect::<HashSet<_>>();
+
+  let mut coalesced: Vec<Event> = vec![];
+  for event in events.into_iter().rev() {
+    let is_short_lived = event.get_window_id().is_some_and(|window_id| short_lived.contains(&window_id));
+    if !is_short_lived && !coalesced.contains(&event) {
+      coalesced.push(event);
+    }
+  }
+  coalesced.reverse();
+
+  coalesced
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::test_dir::TestDir;
+
+  fn created(window_id: usize) -> Event { Event::WindowCreated { process_id: 1, window_id, space: 1 } }
+
+  fn moved(space: usize) -> Event { Event::WindowMoved { space } }
+
+  fn destroyed(window_id: usize) -> Event { Event::WindowDestroyed { window_id } }
+
+  fn focused(window_id: usize) -> Event { Event::WindowFocused { window_id } }
+
+  #[test_log::test]
+  fn coalesce_moves_into_one_per_space() {
+    let events = vec![moved(1), moved(2), moved(1), moved(1), moved(2)];
+    assert_eq!(coalesce_events(events), vec![moved(1), moved(2)]);
+  }
+
+  #[test_log::test]
+  fn coalesce_keeps_last_occurrence() {
+    let events = vec![focused(1), moved(1), focused(2), focused(1), moved(1)];
+    assert_eq!(coalesce_events(events), vec![focused(2), focused(1), moved(1)]);
+  }
+
+  #[test_log::test]
+  fn coalesce_drops_short_lived_windows() {
+    let events = vec![created(1), created(2), focused(1), destroyed(1), destroyed(3)];
+    assert_eq!(coalesce_events(events), vec![created(2), destroyed(3)]);
+  }
+
+  #[test_log::test]
+  fn coalesce_window_events_per_window() {
+    let resized = |window_id| Event::WindowResized { window_id };
+    let events =
+      vec![resized(1), resized(2), resized(1), created(3), Event::WindowMinimized { window_id: 3 }, destroyed(3)];
+    assert_eq!(coalesce_events(events), vec![resized(2), resized(1)]);
+  }
+
+  #[test_log::test]
+  fn debounce_key_is_per_space_or_window() {
+    assert_eq!(moved(2).get_debounce_key(), "window_moved.space-2");
+    assert_eq!(Event::WindowResized { window_id: 7 }.get_debounce_key(), "window_resized.window-7");
+    assert_eq!(Event::YabaiStarted.get_debounce_key(), "yabai_started");
+  }
+
+  #[test_log::test]
+  fn drain_returns_queued_events_once() {
+    let dir = TestDir::new("events-drain");
+    let path = dir.path("events.jsonl");
+    assert!(!has_queued_events(&path));
+    queue_event(&path, &created(1)).unwrap();
+    queue_event(&path, &moved(1)).unwrap();
+    std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"not json\n").unwrap();
+    assert!(has_queued_events(&path));
+
+    assert_eq!(drain_events(&path).unwrap(), vec![created(1), moved(1)]);
+    assert!(!has_queued_events(&path));
+    assert_eq!(drain_events(&path).unwrap(), vec![]);
+  }
+}
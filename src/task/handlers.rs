@@ -1,9 +1,20 @@
 pub mod events {
-  use log::trace;
+  use color_eyre::owo_colors::OwoColorize;
+  use log::{debug, info, trace};
 
   use crate::{
     task::create_initialized_windows_manager::InitializedWindowsManager,
-    window_manager::layout_visibility::LayoutValidity, yabai::config::get_config,
+    window_manager::{
+      layout_visibility::LayoutValidity,
+      window_order::{get_order_without_master, reconcile_window_order},
+    },
+    yabai::{
+      command::{message::YabaiMessage, window_selector::YabaiWindowSelector},
+      config::get_config,
+      spaces::Space,
+      state::State,
+      window::WindowId,
+    },
   };
 
   pub fn on_yabai_start(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
@@ -20,64 +31,270 @@ pub mod events {
     Ok(())
   }
 
-  pub fn window_created(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+  pub fn window_created(
+    iwm: &mut InitializedWindowsManager, process_id: usize, window_id: usize,
+  ) -> color_eyre::Result<()> {
     trace!("Handling window created event");
 
     let wm = &mut iwm.wm;
     let state = &iwm.state;
     let space = &iwm.space;
+    if state.get_monocle(space).is_some() {
+      info!("Monocle is on for space {}, windows are not updated", space.id.blue());
+      return Ok(());
+    }
+    // The new window was inserted at the insertion point, if there was one
+    wm.insertion_point = None;
+    let window = wm.get_window_data(process_id, window_id)?.clone();
+    let space_state = state.get_space(space)?;
     let layout_validity = wm.is_valid_layout(None)?;
+    if let Some(window_order) = wm.layout.engine().get_new_window_order(wm, &window, *space_state)? {
+      wm.window_order = window_order;
+    }
 
-    match layout_validity {
-      LayoutValidity::Valid => {
-        trace!("Layout is valid, no changes were made.");
-        Ok(())
-      },
-      LayoutValidity::Invalid(_) => {
-        trace!("Window created event handled");
-        let process_id: usize = std::env::var("YABAI_PROCESS_ID")?.parse()?;
-        let window_id: usize = std::env::var("YABAI_WINDOW_ID")?.parse()?;
-        let cur_num_master_windows = wm.get_master_windows()?.len();
-        let window = wm.get_window_data(process_id, window_id)?;
-        let space_state = state.get_space(space)?;
-        let config = get_config()?;
-        if config.move_new_windows_to_master {
-          // If the master is full, move a window from master to stack
-          if cur_num_master_windows >= *space_state {
-            let old_master_windows = wm.get_master_window()?;
-            if let Some(old_master_windows) = old_master_windows {
-              wm.move_window_to_stack(&old_master_windows)?;
-            }
-            wm.move_window_to_master(window)?;
-          } else {
-            wm.move_window_to_master(window)?;
-          }
-        } else if cur_num_master_windows > 1 && cur_num_master_windows <= *space_state {
-          trace!("Moving new window {window} to master");
-          wm.move_window_to_master(window)?;
-        } else {
-          trace!("Moving new window {window} to stack");
-          wm.move_window_to_stack(window)?;
-        }
-        wm.update_windows(*space_state)?;
-        Ok(())
-      },
+    if layout_validity == LayoutValidity::Valid && wm.layout.engine().is_new_window_placed(wm, &window, *space_state)? {
+      trace!("Layout is valid and {window} is in its slot, no changes were made.");
+      return wm.update_insertion_point();
     }
+
+    trace!("Placing new window {window}");
+    wm.layout.engine().place_new_window(wm, &window, *space_state)?;
+    wm.update_windows(*space_state)?;
+    trace!("Window created event handled");
+    Ok(())
   }
 
-  pub(crate) fn window_moved(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
-    trace!("Handling window moved event");
+  pub(crate) fn window_focused(iwm: &mut InitializedWindowsManager, window_id: usize) -> color_eyre::Result<()> {
+    trace!("Handling window focused event");
+
+    if !iwm.wm.windows.iter().any(|window| window.id == window_id) {
+      trace!("Window {window_id} is not handled by ymsp, focus history is unchanged");
+      return Ok(());
+    }
+    iwm.state.push_focus(&iwm.space, window_id);
+    // Windows placed relative to the focused window go next to the newly focused one
+    iwm.wm.update_insertion_point()?;
+    trace!("Window focused event handled");
+
+    Ok(())
+  }
+
+  pub(crate) fn window_destroyed(iwm: &mut InitializedWindowsManager, window_id: usize) -> color_eyre::Result<()> {
+    trace!("Handling window destroyed event");
+
+    let previous_focus = get_previous_focus(iwm.state.get_focus_history(&iwm.space), window_id);
+    iwm.state.remove_focus(&iwm.space, window_id);
+    iwm.wm.focus_history = iwm.state.get_focus_history(&iwm.space).to_vec();
+
+    // The saved window order still has the destroyed window in its slot, unless ymsp closed it
+    let window_order = iwm.state.get_window_order(&iwm.space).to_vec();
+    let master_position = get_master_position(&window_order, window_id, *iwm.state.get_space(&iwm.space)?);
+    if iwm.state.get_monocle(&iwm.space).is_some() {
+      info!("Monocle is on for space {}, windows are not updated", iwm.space.id.blue());
+    } else if let Some(position) = master_position {
+      info!("Master window {} was destroyed", window_id.blue());
+      retile_without_master(iwm, &window_order, position)?;
+    } else {
+      iwm.wm.update_windows(*iwm.state.get_space(&iwm.space)?)?;
+    }
+
+    let wm = &mut iwm.wm;
+
+    // Focus goes back to the most recently used window rather than the one picked by macOS, without switching spaces
+    if let Some(previous_focus) = previous_focus.filter(|_| iwm.space.has_focus) {
+      let window_to_focus =
+        previous_focus.iter().find_map(|id| wm.windows.iter().find(|window| window.id == *id)).cloned();
+      if let Some(window_to_focus) = window_to_focus {
+        info!("Focusing most recently used window {window_to_focus}");
+        let message = YabaiMessage::current_window().focus(YabaiWindowSelector::Id(window_to_focus.id))?;
+        wm.send_yabai_message(message)?;
+      }
+    }
+    trace!("Window destroyed event handled");
+
+    Ok(())
+  }
+
+  /// Slot of `window_id` in `window_order`, if it is a master window.
+  pub(crate) fn get_master_position(
+    window_order: &[WindowId], window_id: WindowId, master_count: usize,
+  ) -> Option<usize> {
+    window_order.iter().position(|id| *id == window_id).filter(|position| *position < master_count)
+  }
+
+  /// Re-tiles the space once the master window in slot `position` of `window_order` is gone.
+  /// A stack window takes its slot, or the master count shrinks, according to the `masterPromotion` configuration.
+  pub(crate) fn retile_without_master(
+    iwm: &mut InitializedWindowsManager, window_order: &[WindowId], position: usize,
+  ) -> color_eyre::Result<()> {
+    let config = get_config()?;
+    let (window_order, master_count) = get_order_without_master(
+      window_order,
+      position,
+      *iwm.state.get_space(&iwm.space)?,
+      config.master_promotion,
+      iwm.state.get_focus_history(&iwm.space),
+    );
+    debug!("Promoting with {:?}, {} master windows left", config.master_promotion, master_count.blue());
+    *iwm.state.get_space_mut(&iwm.space)? = master_count;
+
+    let wm = &mut iwm.wm;
+    wm.expected_current_num_master_windows = master_count;
+    wm.window_order = reconcile_window_order(&window_order, &wm.window_order);
+    let windows = wm.get_windows_in_order();
+    let master_count = master_count.min(windows.len()).max(1);
+    wm.arrange_windows_in_order(windows, master_count)
+  }
+
+  /// Windows focused before `window_id`, most recent first, if it was focused when it was destroyed.
+  /// macOS may have focused another window before yabai reported the destruction, so the destroyed window counts as
+  /// focused among the two most recent focuses, and the window picked by macOS is skipped.
+  fn get_previous_focus(history: &[usize], window_id: usize) -> Option<Vec<usize>> {
+    let position = history.iter().take(2).position(|id| *id == window_id)?;
+    Some(history[position + 1..].to_vec())
+  }
 
+  /// Re-tiles the space, unless it is in monocle.
+  pub(crate) fn retile_space(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
     let wm = &mut iwm.wm;
     let state = &iwm.state;
     let space = &iwm.space;
+    if state.get_monocle(space).is_some() {
+      info!("Monocle is on for space {}, windows are not updated", space.id.blue());
+      return Ok(());
+    }
     let space_state = state.get_space(space)?;
-    trace!("Updating windows...");
-    wm.update_windows(*space_state)?;
+    trace!("Updating windows of space {}...", space.index.blue());
+    wm.update_windows(*space_state)
+  }
+
+  pub(crate) fn window_moved(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    trace!("Handling window moved event");
+    retile_space(iwm)?;
     trace!("Window moved event handled");
 
     Ok(())
   }
+
+  /// The spaces shown on a display, which are the ones with windows on screen.
+  pub(crate) fn get_visible_spaces() -> color_eyre::Result<Vec<Space>> {
+    let spaces = YabaiMessage::query().spaces()?;
+    Ok(spaces.into_iter().filter(|space| space.is_visible != 0 && !space.is_native_fullscreen).collect())
+  }
+
+  /// The space of the window, or the visible spaces when the window can't be found anymore.
+  pub(crate) fn get_window_spaces(window_id: usize) -> color_eyre::Result<Vec<Space>> {
+    let Ok(window) = YabaiMessage::query().window(window_id) else {
+      debug!("Window {} is gone, every visible space is updated", window_id.blue());
+      return get_visible_spaces();
+    };
+    let spaces = YabaiMessage::query().spaces()?;
+    Ok(spaces.into_iter().filter(|space| space.index == window.space).collect())
+  }
+
+  /// The spaces whose saved window order has `window_id`, or the visible spaces when none has it.
+  /// A destroyed window can't be queried from yabai anymore.
+  pub(crate) fn get_destroyed_window_spaces(window_id: WindowId) -> color_eyre::Result<Vec<Space>> {
+    let state = State::read_state()?;
+    let spaces = get_spaces_with_window(&state, YabaiMessage::query().spaces()?, window_id);
+    if spaces.is_empty() {
+      debug!("Window {} is in no saved window order, every visible space is updated", window_id.blue());
+      return get_visible_spaces();
+    }
+
+    Ok(spaces)
+  }
+
+  fn get_spaces_with_window(state: &State, spaces: Vec<Space>, window_id: WindowId) -> Vec<Space> {
+    spaces.into_iter().filter(|space| state.get_window_order(space).contains(&window_id)).collect()
+  }
+
+  /// The space at `index`, if it still exists.
+  pub(crate) fn get_space_by_index(index: usize) -> color_eyre::Result<Vec<Space>> {
+    let spaces = YabaiMessage::query().spaces()?;
+    Ok(spaces.into_iter().filter(|space| space.index == index).collect())
+  }
+
+  /// The space with the yabai id `space_id`.
+  pub(crate) fn get_space_by_id(space_id: usize) -> color_eyre::Result<Vec<Space>> {
+    let spaces = YabaiMessage::query().spaces()?;
+    Ok(spaces.into_iter().filter(|space| space.id == space_id).collect())
+  }
+
+  #[cfg(test)]
+  mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+      window_manager::{layouts::Layout, WindowsManager},
+      yabai::{
+        display::Display,
+        frame::Frame,
+        state::{Monocle, State},
+      },
+    };
+
+    /// A space zoomed by `toggle-monocle`. Its windows are never read from yabai, so any yabai call fails.
+    fn get_monocle_space() -> InitializedWindowsManager {
+      let space = Space { id: 1, index: 1, label: "code".to_string(), ..Default::default() };
+      let frame = Frame { x: 0.0, y: 0.0, w: 1920.0, h: 1080.0 };
+      let display = Display { id: 1, uuid: uuid::Uuid::nil(), index: 1, frame, spaces: vec![1] };
+      let mut state = State::default();
+      state.set_monocle(&space, Monocle { window: 1, master_count: 1, windows: vec![1, 2] });
+      let wm = WindowsManager::new(display.clone(), space.clone(), 1, None, Layout::Monocle, vec![1, 2]);
+
+      InitializedWindowsManager { wm, state, display, space }
+    }
+
+    #[test]
+    fn window_created_is_suspended_during_monocle() {
+      let mut iwm = get_monocle_space();
+      window_created(&mut iwm, 42, 3).unwrap();
+      assert_eq!(iwm.wm.window_order, vec![1, 2]);
+    }
+
+    #[test]
+    fn window_moved_is_suspended_during_monocle() {
+      let mut iwm = get_monocle_space();
+      window_moved(&mut iwm).unwrap();
+      assert_eq!(iwm.wm.window_order, vec![1, 2]);
+    }
+
+    #[test]
+    fn destroyed_window_is_found_in_the_saved_window_orders() {
+      let code = Space { id: 1, index: 1, label: "code".to_string(), ..Default::default() };
+      let web = Space { id: 2, index: 2, label: "web".to_string(), ..Default::default() };
+      let mut state = State::default();
+      state.set_window_order(&code, vec![1, 2]);
+      state.set_window_order(&web, vec![3, 4]);
+      let spaces = get_spaces_with_window(&state, vec![code, web.clone()], 4);
+      assert_eq!(spaces.iter().map(|space| space.id).collect::<Vec<_>>(), vec![web.id]);
+    }
+
+    #[test]
+    fn only_master_windows_have_a_master_position() {
+      assert_eq!(get_master_position(&[1, 2, 3], 2, 2), Some(1));
+      assert_eq!(get_master_position(&[1, 2, 3], 3, 2), None);
+      // Closed by ymsp, the saved order no longer has the window
+      assert_eq!(get_master_position(&[1, 3], 2, 2), None);
+    }
+
+    #[test]
+    fn window_picked_by_macos_is_not_refocused() {
+      assert_eq!(get_previous_focus(&[1, 2, 3], 2), Some(vec![3]));
+    }
+
+    #[test]
+    fn focused_window_is_refocused_from_the_previous_focus() {
+      assert_eq!(get_previous_focus(&[2, 3, 1], 2), Some(vec![3, 1]));
+    }
+
+    #[test]
+    fn unfocused_window_is_not_refocused() {
+      assert_eq!(get_previous_focus(&[1, 3, 2], 2), None);
+    }
+  }
 }
 
 pub(crate) mod focus {
@@ -86,10 +303,13 @@ pub(crate) mod focus {
 
   use crate::{
     task::create_initialized_windows_manager::InitializedWindowsManager,
-    window_manager::WindowsManager,
+    window_manager::{
+      window_position::{compare_position, compare_stack_position},
+      WindowsManager,
+    },
     yabai::{
       command::{
-        direction_selector::YabaiDirectionSelector, message::YabaiMessage, to_command::Runnable,
+        message::YabaiMessage, stack_selector::YabaiStackSelector, to_command::Runnable,
         window_selector::YabaiWindowSelector,
       },
       config::get_config,
@@ -118,24 +338,16 @@ pub(crate) mod focus {
     }
   }
 
-  pub(crate) fn focus_up_window(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+  /// Focus the window `offset` slots away from the focused window in the window order of the space
+  fn focus_window_at_offset(iwm: &mut InitializedWindowsManager, offset: isize) -> color_eyre::Result<()> {
     let wm = &mut iwm.wm;
     let focused_window = wm.get_focused_window();
 
     if let Some(focused_window) = focused_window {
-      if wm.is_master_window(focused_window)? && wm.is_top_window(wm.get_master_windows()?, focused_window) {
-        let window_to_focus = wm.get_bottom_stack_window().or(wm.get_bottom_master_window()?);
-
-        _focus_window(wm, window_to_focus)
-      } else if wm.is_stack_window(focused_window)? && wm.is_top_window(wm.get_stack_windows(), focused_window) {
-        let window_to_focus = wm.get_bottom_master_window()?;
+      let window_to_focus = wm.get_window_at_offset(focused_window, offset);
+      trace!("Focusing window {offset} slots away from {focused_window}");
 
-        _focus_window(wm, window_to_focus)
-      } else {
-        trace!("Focusing north window");
-        let message = YabaiMessage::current_window().focus(YabaiDirectionSelector::North)?;
-        wm.send_yabai_message(message)
-      }
+      _focus_window(wm, window_to_focus)
     } else {
       trace!("No focused window, focusing first window");
       let message = YabaiMessage::current_window().focus(YabaiWindowSelector::First)?;
@@ -143,29 +355,104 @@ pub(crate) mod focus {
     }
   }
 
+  /// Focus the previous window, the bottom stack window when the top master window is focused
+  pub(crate) fn focus_up_window(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    focus_window_at_offset(iwm, -1)
+  }
+
+  /// Focus the next window, the top master window when the bottom stack window is focused
   pub(crate) fn focus_down_window(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
-    let wm = &mut iwm.wm;
+    focus_window_at_offset(iwm, 1)
+  }
+
+  /// Focus the `slot`-th window of `windows`, sorted from the first slot, starting at 1
+  fn focus_slot(wm: &WindowsManager, windows: Vec<Window>, slot: usize) -> color_eyre::Result<()> {
+    let window_to_focus = slot.checked_sub(1).and_then(|index| windows.get(index)).cloned();
+    if window_to_focus.is_none() {
+      debug!("No window in slot {slot} of {len} windows", len = windows.len());
+    }
+
+    _focus_window(wm, window_to_focus)
+  }
+
+  /// Focus the `slot`-th master window from the top
+  pub(crate) fn focus_master(iwm: &mut InitializedWindowsManager, slot: usize) -> color_eyre::Result<()> {
+    let wm = &iwm.wm;
+    let mut master_windows = wm.get_master_windows()?;
+    master_windows.sort_by(compare_position);
+    focus_slot(wm, master_windows, slot)
+  }
+
+  /// Focus the `slot`-th stack window from the top, going through the stack columns one after the other
+  pub(crate) fn focus_stack(iwm: &mut InitializedWindowsManager, slot: usize) -> color_eyre::Result<()> {
+    let wm = &iwm.wm;
+    let config = get_config()?;
+    let mut stack_windows = wm.get_stack_windows();
+    stack_windows.sort_by(|w1, w2| compare_stack_position(&config.master_position, w1, w2));
+    focus_slot(wm, stack_windows, slot)
+  }
+
+  /// Focus the top stack window
+  pub(crate) fn focus_stack_top(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let wm = &iwm.wm;
+    _focus_window(wm, wm.get_top_stack_window())
+  }
+
+  /// Focus the bottom stack window
+  pub(crate) fn focus_stack_bottom(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let wm = &iwm.wm;
+    _focus_window(wm, wm.get_bottom_stack_window())
+  }
+
+  /// Focus the most recently focused window, other than the focused one, among `windows`
+  fn focus_last_of(iwm: &InitializedWindowsManager, windows: Vec<Window>) -> color_eyre::Result<()> {
+    let wm = &iwm.wm;
     let focused_window = wm.get_focused_window();
+    let window_to_focus = iwm
+      .state
+      .get_focus_history(&iwm.space)
+      .iter()
+      .filter(|id| focused_window.is_none_or(|focused_window| focused_window.id != **id))
+      .find_map(|id| windows.iter().find(|window| window.id == *id))
+      .cloned();
 
-    if let Some(focused_window) = focused_window {
-      if wm.is_master_window(focused_window)? && wm.is_bottom_window(wm.get_master_windows()?, focused_window) {
-        let window_to_focus = wm.get_top_stack_window().or(wm.get_top_master_window()?);
+    _focus_window(wm, window_to_focus)
+  }
 
-        _focus_window(wm, window_to_focus)
-      } else if wm.is_stack_window(focused_window)? && wm.is_bottom_window(wm.get_stack_windows(), focused_window) {
-        let window_to_focus = wm.get_top_master_window()?;
+  /// Focus the previously focused window of the space
+  pub(crate) fn focus_last(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    focus_last_of(iwm, iwm.wm.windows.clone())
+  }
 
-        _focus_window(wm, window_to_focus)
-      } else {
-        trace!("Focusing south window");
-        let message = YabaiMessage::current_window().focus(YabaiDirectionSelector::South)?;
-        wm.send_yabai_message(message)
-      }
-    } else {
-      trace!("No focused window, focusing first window");
-      let message = YabaiMessage::current_window().focus(YabaiWindowSelector::First)?;
-      wm.send_yabai_message(message)
+  /// Focus the most recently focused master window
+  pub(crate) fn focus_last_master(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    focus_last_of(iwm, iwm.wm.get_master_windows()?)
+  }
+
+  /// Focus the most recently focused stack window
+  pub(crate) fn focus_last_stack(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    focus_last_of(iwm, iwm.wm.get_stack_windows())
+  }
+
+  /// Focus a window of the stack of the focused window, wrapping around to `fallback` at the end of the stack
+  fn focus_in_stack(selector: YabaiStackSelector, fallback: YabaiStackSelector) -> color_eyre::Result<()> {
+    let message = YabaiMessage::current_window().focus(selector)?;
+    if let Err(err) = message.run() {
+      debug!("Unable to focus window in stack, wrapping around: {err}");
+      YabaiMessage::current_window().focus(fallback)?.run()?;
     }
+
+    Ok(())
+  }
+
+  /// Focus the next window in the stack of the focused window
+  pub(crate) fn focus_next_in_stack() -> color_eyre::Result<()> {
+    focus_in_stack(YabaiStackSelector::Next, YabaiStackSelector::First)
+  }
+
+  /// Focus the previous window in the stack of the focused window
+  pub(crate) fn focus_prev_in_stack() -> color_eyre::Result<()> {
+    focus_in_stack(YabaiStackSelector::Prev, YabaiStackSelector::Last)
   }
 
   /// Focus the next display
@@ -181,10 +468,10 @@ pub(crate) mod focus {
         trace!("Focusing next display: {next_display}");
         YabaiMessage::current_display().focus(next_display)?.run().map(|_| ())
       } else {
-        bail!("Could not find next display in displays: {displays:?}")
+        bail!("Could not find next display in displays: {displays:?}");
       }
     } else {
-      bail!("Could not find focused display in displays: {displays:?}")
+      bail!("Could not find focused display in displays: {displays:?}");
     }
   }
 
@@ -201,10 +488,10 @@ pub(crate) mod focus {
         trace!("Focusing previous display: {previous_display}");
         YabaiMessage::current_display().focus(previous_display)?.run().map(|_| ())
       } else {
-        bail!("Could not find previous display in displays: {displays:?}")
+        bail!("Could not find previous display in displays: {displays:?}");
       }
     } else {
-      bail!("Could not find focused display in displays: {displays:?}")
+      bail!("Could not find focused display in displays: {displays:?}");
     }
   }
 }
@@ -230,7 +517,7 @@ pub(crate) mod window_count {
       trace!("Increased master window count to {}", current_state);
       state.write_state()
     } else {
-      bail!("Cannot increase master window count above or equals to the number of windows in the space")
+      bail!("Cannot increase master window count above or equals to the number of windows in the space");
     }
   }
 
@@ -251,6 +538,303 @@ pub(crate) mod window_count {
   }
 }
 
+pub(crate) mod master_ratio {
+  use color_eyre::owo_colors::OwoColorize;
+  use log::{debug, info};
+
+  use crate::{
+    task::create_initialized_windows_manager::InitializedWindowsManager,
+    window_manager::master_ratio::{MAX_MASTER_RATIO, MIN_MASTER_RATIO},
+    yabai::config::get_config,
+  };
+
+  pub(crate) fn set_master_ratio(iwm: &mut InitializedWindowsManager, ratio: f64) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &mut iwm.state;
+    let space = &iwm.space;
+
+    let ratio = ratio.clamp(MIN_MASTER_RATIO, MAX_MASTER_RATIO);
+    info!("Setting master ratio to {}", ratio.blue());
+    wm.master_ratio = Some(ratio);
+    wm.apply_master_ratio()?;
+    state.set_ratio(space, ratio);
+    state.write_state()
+  }
+
+  fn change_master_ratio(iwm: &mut InitializedWindowsManager, delta: f64) -> color_eyre::Result<()> {
+    let current_ratio = match iwm.wm.master_ratio {
+      Some(ratio) => ratio,
+      None => iwm.wm.get_master_ratio()?.unwrap_or(0.5),
+    };
+    debug!("Current master ratio: {}", current_ratio.blue());
+
+    set_master_ratio(iwm, current_ratio + delta)
+  }
+
+  pub(crate) fn grow_master(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let config = get_config()?;
+    change_master_ratio(iwm, config.master_ratio_step)
+  }
+
+  pub(crate) fn shrink_master(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let config = get_config()?;
+    change_master_ratio(iwm, -config.master_ratio_step)
+  }
+}
+
+pub(crate) mod layout {
+  use log::info;
+
+  use crate::{task::create_initialized_windows_manager::InitializedWindowsManager, window_manager::layouts::Layout};
+
+  pub(crate) fn set_layout(iwm: &mut InitializedWindowsManager, layout: Layout) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &mut iwm.state;
+    let space = &iwm.space;
+
+    info!("Switching layout from {} to {layout}", wm.layout);
+    let space_state = *state.get_space(space)?;
+    wm.layout = layout;
+    wm.reset_windows(space_state)?;
+    state.set_layout(space, layout);
+    state.write_state()
+  }
+
+  pub(crate) fn cycle_layout(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let layout = iwm.wm.layout.next();
+    set_layout(iwm, layout)
+  }
+}
+
+pub(crate) mod monocle {
+  use color_eyre::{eyre::eyre, owo_colors::OwoColorize};
+  use log::{debug, info};
+
+  use crate::{
+    task::create_initialized_windows_manager::InitializedWindowsManager,
+    window_manager::layout_visibility::LayoutValidity,
+    yabai::{
+      command::{message::YabaiMessage, toggle_selector::YabaiToggleSelector},
+      state::Monocle,
+    },
+  };
+
+  fn enter_monocle(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &mut iwm.state;
+    let space = &iwm.space;
+
+    let focused_window = wm.get_focused_window().cloned().ok_or(eyre!("No focused window to zoom"))?;
+    let monocle = Monocle {
+      window: focused_window.id,
+      master_count: *state.get_space(space)?,
+      windows: wm.get_ordered_windows()?.iter().map(|window| window.id).collect(),
+    };
+    debug!("Saving arrangement {monocle:?}");
+
+    if !focused_window.has_fullscreen_zoom {
+      info!("Zooming {focused_window}");
+      let message = YabaiMessage::window(&focused_window).toggle(YabaiToggleSelector::ZoomFullscreen)?;
+      wm.send_yabai_message(message)?;
+    }
+    state.set_monocle(space, monocle);
+    state.write_state()
+  }
+
+  fn exit_monocle(iwm: &mut InitializedWindowsManager, monocle: Monocle) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &mut iwm.state;
+    let space = &iwm.space;
+
+    let zoomed_window = wm.windows.iter().find(|window| window.id == monocle.window && window.has_fullscreen_zoom);
+    if let Some(zoomed_window) = zoomed_window {
+      info!("Unzooming {zoomed_window}");
+      let message = YabaiMessage::window(zoomed_window).toggle(YabaiToggleSelector::ZoomFullscreen)?;
+      wm.send_yabai_message(message)?;
+      wm.windows = wm.get_windows_data()?;
+    }
+
+    let master_count = monocle.master_count.clamp(1, wm.windows.len().max(1));
+    info!("Restoring {} master windows", master_count.blue());
+    *state.get_space_mut(space)? = master_count;
+    wm.expected_current_num_master_windows = master_count;
+
+    // Windows that still exist go back to their slot, windows created in the meantime go after them
+    let current_windows = wm.get_ordered_windows()?;
+    let mut windows = monocle
+      .windows
+      .iter()
+      .filter_map(|id| current_windows.iter().find(|window| window.id == *id))
+      .cloned()
+      .collect::<Vec<_>>();
+    for window in &current_windows {
+      if !windows.iter().any(|w| w.id == window.id) {
+        windows.push(window.clone());
+      }
+    }
+
+    let is_same_order = windows.iter().map(|window| window.id).eq(current_windows.iter().map(|window| window.id));
+    if is_same_order && wm.is_valid_layout(Some(master_count))? == LayoutValidity::Valid {
+      debug!("Arrangement is unchanged, no need to restore it");
+    } else {
+      wm.arrange_windows_in_order(windows, master_count)?;
+    }
+
+    state.write_state()
+  }
+
+  pub(crate) fn toggle_monocle(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    match iwm.state.take_monocle(&iwm.space) {
+      Some(monocle) => exit_monocle(iwm, monocle),
+      None => enter_monocle(iwm),
+    }
+  }
+}
+
+pub(crate) mod rotate {
+  use color_eyre::owo_colors::OwoColorize;
+  use log::{debug, info};
+
+  use crate::{
+    task::create_initialized_windows_manager::InitializedWindowsManager,
+    window_manager::window_order::get_rotated_window_order,
+    yabai::command::{message::YabaiMessage, window_selector::YabaiWindowSelector},
+  };
+
+  /// Shift every window `offset` slots through the master and stack order, keeping the focus on the same window
+  fn rotate_windows(iwm: &mut InitializedWindowsManager, offset: isize) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &iwm.state;
+    let space = &iwm.space;
+    if state.get_monocle(space).is_some() {
+      info!("Monocle is on for space {}, windows are not rotated", space.id.blue());
+      return Ok(());
+    }
+
+    let focused_window = wm.get_focused_window().cloned();
+    if wm.window_order.len() < 2 {
+      debug!("Not enough windows to rotate");
+      return Ok(());
+    }
+    let window_order = get_rotated_window_order(&wm.window_order, *state.get_space(space)?, offset);
+    info!("Rotating windows by {offset}: {window_order:?}");
+    wm.apply_window_order(window_order)?;
+
+    if let Some(focused_window) = focused_window {
+      let message = YabaiMessage::current_window().focus(YabaiWindowSelector::Id(focused_window.id))?;
+      wm.send_yabai_message(message)?;
+    }
+
+    Ok(())
+  }
+
+  /// The first stack window becomes the first master window, and the last master window goes to the bottom of the stack
+  pub(crate) fn rotate_next(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> { rotate_windows(iwm, 1) }
+
+  /// The bottom stack window becomes the last master window, and the first master window goes to the top of the stack
+  pub(crate) fn rotate_prev(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> { rotate_windows(iwm, -1) }
+}
+
+pub(crate) mod swap {
+  use color_eyre::{eyre::eyre, owo_colors::OwoColorize};
+  use log::{debug, info};
+
+  use crate::{
+    task::create_initialized_windows_manager::InitializedWindowsManager,
+    yabai::command::{message::YabaiMessage, window_selector::YabaiWindowSelector},
+  };
+
+  /// Swap the focused window with the window `offset` slots away, wrapping around like the focus commands
+  fn swap_focused_window(iwm: &mut InitializedWindowsManager, offset: isize) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &iwm.state;
+    let space = &iwm.space;
+    if state.get_monocle(space).is_some() {
+      info!("Monocle is on for space {}, windows are not swapped", space.id.blue());
+      return Ok(());
+    }
+
+    let focused_window = wm.get_focused_window().cloned().ok_or(eyre!("No focused window to swap"))?;
+    let Some(other_window) = wm.get_window_at_offset(&focused_window, offset).filter(|w| w.id != focused_window.id)
+    else {
+      debug!("No window to swap {focused_window} with");
+      return Ok(());
+    };
+
+    let mut window_order = wm.window_order.clone();
+    let position = window_order.iter().position(|id| *id == focused_window.id);
+    let other_position = window_order.iter().position(|id| *id == other_window.id);
+    if let (Some(position), Some(other_position)) = (position, other_position) {
+      window_order.swap(position, other_position);
+    }
+    info!("Swapping {focused_window} with {other_window}");
+    wm.apply_window_order(window_order)?;
+
+    let message = YabaiMessage::current_window().focus(YabaiWindowSelector::Id(focused_window.id))?;
+    wm.send_yabai_message(message)
+  }
+
+  /// Move the focused window one slot up, to the bottom of the stack from the top master slot
+  pub(crate) fn swap_up(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> { swap_focused_window(iwm, -1) }
+
+  /// Move the focused window one slot down, to the top master slot from the bottom of the stack
+  pub(crate) fn swap_down(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> { swap_focused_window(iwm, 1) }
+}
+
+pub(crate) mod zoom {
+  use color_eyre::{eyre::eyre, owo_colors::OwoColorize};
+  use log::{debug, info};
+
+  use crate::{
+    task::create_initialized_windows_manager::InitializedWindowsManager,
+    yabai::command::{message::YabaiMessage, window_selector::YabaiWindowSelector},
+  };
+
+  /// Swap the focused window with the first master window.
+  ///
+  /// When the focused window is already the first master window, it is swapped with the window it displaced with the
+  /// previous zoom, or with the top stack window.
+  pub(crate) fn zoom(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &mut iwm.state;
+    let space = &iwm.space;
+    if state.get_monocle(space).is_some() {
+      info!("Monocle is on for space {}, windows are not zoomed", space.id.blue());
+      return Ok(());
+    }
+
+    let focused_window = wm.get_focused_window().cloned().ok_or(eyre!("No focused window to zoom"))?;
+    let mut window_order = wm.window_order.clone();
+    let Some(&master_window) = window_order.first() else {
+      debug!("No windows to zoom");
+      return Ok(());
+    };
+
+    let window = if focused_window.id == master_window {
+      // Back to the window displaced by the previous zoom, if it is still in the space
+      let Some(window) = state
+        .get_zoom(space)
+        .filter(|id| *id != master_window && window_order.contains(id))
+        .or(window_order.get(wm.expected_current_num_master_windows).copied())
+      else {
+        debug!("No stack window to zoom");
+        return Ok(());
+      };
+      window
+    } else {
+      focused_window.id
+    };
+    let position = window_order.iter().position(|id| *id == window).ok_or(eyre!("Window {window} not found"))?;
+    window_order.swap(0, position);
+    info!("Zooming window {} in place of {}", window.blue(), master_window.blue());
+    wm.apply_window_order(window_order)?;
+    state.set_zoom(space, master_window);
+
+    let message = YabaiMessage::current_window().focus(YabaiWindowSelector::Id(window))?;
+    wm.send_yabai_message(message)
+  }
+}
+
 pub(crate) mod move_window {
   use color_eyre::eyre::bail;
   use log::{info, trace};
@@ -285,10 +869,10 @@ pub(crate) mod move_window {
         trace!("Moving window to next display: {next_display}");
         YabaiMessage::current_window().display(next_display)?.run().map(|_| ())
       } else {
-        bail!("Could not find next display in displays: {displays:?}")
+        bail!("Could not find next display in displays: {displays:?}");
       }
     } else {
-      bail!("Could not find focused display in displays: {displays:?}")
+      bail!("Could not find focused display in displays: {displays:?}");
     }
   }
   pub(crate) fn move_window_to_previous_display() -> color_eyre::Result<()> {
@@ -303,13 +887,236 @@ pub(crate) mod move_window {
         trace!("Moving window to previous display: {previous_display}");
         YabaiMessage::current_window().display(previous_display)?.run().map(|_| ())
       } else {
-        bail!("Could not find previous display in displays: {displays:?}")
+        bail!("Could not find previous display in displays: {displays:?}");
+      }
+    } else {
+      bail!("Could not find focused display in displays: {displays:?}");
+    }
+  }
+}
+
+pub(crate) mod close {
+  use std::time::Duration;
+
+  use color_eyre::owo_colors::OwoColorize;
+  use log::{debug, info};
+
+  use crate::{
+    task::{
+      create_initialized_windows_manager::InitializedWindowsManager,
+      handlers::events::{get_master_position, retile_without_master},
+    },
+    yabai::{
+      command::{message::YabaiMessage, window_selector::YabaiWindowSelector},
+      config::{get_config, CloseFocusPolicy},
+      window::WindowId,
+    },
+  };
+
+  /// How many times yabai is asked whether the closed window is gone.
+  const CLOSE_POLL_ATTEMPTS: usize = 10;
+  /// Delay between two attempts.
+  const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+  /// The window to focus after the window in slot `position` of the window order was closed.
+  ///
+  /// `window_order` and `focus_history` no longer have the closed window.
+  fn get_window_to_focus(
+    policy: CloseFocusPolicy, window_order: &[WindowId], position: Option<usize>, focus_history: &[WindowId],
+  ) -> Option<WindowId> {
+    match policy {
+      // The window that followed the closed window now takes its slot
+      CloseFocusPolicy::NextInStack => window_order.get(position.unwrap_or_default()).or(window_order.last()).copied(),
+      CloseFocusPolicy::Master => window_order.first().copied(),
+      CloseFocusPolicy::LastFocused => focus_history.iter().find(|id| window_order.contains(id)).copied(),
+    }
+  }
+
+  /// Close the focused window, then focus another window according to the `closeFocusPolicy` configuration and
+  /// re-tile the space.
+  pub(crate) fn close_focused_window(iwm: &mut InitializedWindowsManager) -> color_eyre::Result<()> {
+    let wm = &mut iwm.wm;
+    let state = &mut iwm.state;
+    let space = &iwm.space;
+    let Some(focused_window) = wm.get_focused_window().cloned() else {
+      debug!("No focused window to close");
+      return Ok(());
+    };
+    let window_order = wm.window_order.clone();
+    let position = window_order.iter().position(|id| *id == focused_window.id);
+    let master_position = get_master_position(&window_order, focused_window.id, *state.get_space(space)?);
+
+    info!("Closing {focused_window}");
+    let message = YabaiMessage::window(&focused_window).close(None)?;
+    wm.send_yabai_message(message)?;
+
+    // yabai can still report the window for a short while after it was closed
+    for _ in 0..CLOSE_POLL_ATTEMPTS {
+      wm.windows = wm.get_windows_data()?;
+      if !wm.windows.iter().any(|window| window.id == focused_window.id) {
+        break;
       }
+      std::thread::sleep(CLOSE_POLL_INTERVAL);
+    }
+    if wm.windows.iter().any(|window| window.id == focused_window.id) {
+      // e.g. the application asks whether to save changes, the space is updated when the window is destroyed
+      info!("{focused_window} is still open, windows are not updated");
+      return Ok(());
+    }
+    state.remove_focus(space, focused_window.id);
+    wm.reconcile_window_order()?;
+
+    if state.get_monocle(space).is_some() {
+      info!("Monocle is on for space {}, windows are not updated", space.id.blue());
+    } else if let Some(master_position) = master_position {
+      // The destroyed event comes after the state is saved without the window, so the promotion happens here
+      retile_without_master(iwm, &window_order, master_position)?;
     } else {
-      bail!("Could not find focused display in displays: {displays:?}")
+      let master_count = (*state.get_space(space)?).min(wm.windows.len()).max(1);
+      wm.update_windows(master_count)?;
+    }
+
+    let config = get_config()?;
+    let window_to_focus = get_window_to_focus(
+      config.close_focus_policy,
+      &iwm.wm.window_order,
+      position,
+      iwm.state.get_focus_history(&iwm.space),
+    );
+    if let Some(window_to_focus) = window_to_focus {
+      debug!("Focusing window {window_to_focus} after closing {focused_window}");
+      let message = YabaiMessage::current_window().focus(YabaiWindowSelector::Id(window_to_focus))?;
+      iwm.wm.send_yabai_message(message)?;
+    }
+
+    Ok(())
+  }
+
+  #[cfg(test)]
+  mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn focuses_window_taking_the_slot_of_the_closed_window() {
+      assert_eq!(get_window_to_focus(CloseFocusPolicy::NextInStack, &[1, 3, 4], Some(1), &[4]), Some(3));
+      assert_eq!(get_window_to_focus(CloseFocusPolicy::NextInStack, &[1, 3], Some(2), &[]), Some(3));
+    }
+
+    #[test]
+    fn focuses_first_master_window() {
+      assert_eq!(get_window_to_focus(CloseFocusPolicy::Master, &[1, 3, 4], Some(1), &[4]), Some(1));
+    }
+
+    #[test]
+    fn focuses_most_recently_focused_window_still_open() {
+      assert_eq!(get_window_to_focus(CloseFocusPolicy::LastFocused, &[1, 3, 4], Some(1), &[7, 4, 1]), Some(4));
+      assert_eq!(get_window_to_focus(CloseFocusPolicy::LastFocused, &[1, 3], None, &[]), None);
+    }
+  }
+}
+
+pub(crate) mod state {
+  use color_eyre::{eyre::eyre, owo_colors::OwoColorize};
+  use log::{debug, info};
+
+  use crate::{
+    task::{StateResetArgs, StateSetArgs, StateShowArgs},
+    yabai::{
+      command::message::YabaiMessage,
+      spaces::Space,
+      state::{get_space_key, State},
+    },
+  };
+
+  /// Find the space with the given mission-control index or label, or the focused space.
+  fn find_space<'a>(spaces: &'a [Space], selector: Option<&str>) -> color_eyre::Result<&'a Space> {
+    let space = match selector {
+      Some(selector) => {
+        spaces
+          .iter()
+          .find(|space| space.label == selector || selector.parse::<usize>().is_ok_and(|index| space.index == index))
+      },
+      None => spaces.iter().find(|space| space.has_focus),
+    };
+
+    space.ok_or(eyre!("Unable to find the space {selector:?}"))
+  }
+
+  pub(crate) fn show_state(args: &StateShowArgs) -> color_eyre::Result<()> {
+    let state = State::read_state()?;
+    if args.json {
+      println!("{}", serde_json::to_string_pretty(&state)?);
+      return Ok(());
+    }
+
+    let spaces = YabaiMessage::query().spaces()?;
+    let mut keys = state.get_spaces().keys().collect::<Vec<_>>();
+    keys.sort();
+    println!(
+      "{:<38} {:>5} {:>6} {:>8} {:>6} {:>6} {:>7}",
+      "SPACE", "INDEX", "MASTER", "LAYOUT", "RATIO", "PAUSED", "WINDOWS"
+    );
+    for key in keys {
+      let space_state = &state.get_spaces()[key];
+      let index = spaces
+        .iter()
+        .find(|space| get_space_key(space) == *key)
+        .map(|space| space.index.to_string())
+        .unwrap_or("-".to_string());
+      let ratio = space_state.ratio.map(|ratio| format!("{ratio:.2}")).unwrap_or("-".to_string());
+      println!(
+        "{key:<38} {index:>5} {:>6} {:>8} {ratio:>6} {:>6} {:>7}",
+        space_state.master_count,
+        format!("{:?}", space_state.layout),
+        space_state.paused,
+        space_state.window_order.len()
+      );
+    }
+
+    Ok(())
+  }
+
+  pub(crate) fn set_state(args: &StateSetArgs) -> color_eyre::Result<()> {
+    let mut state = State::read_state()?;
+    let spaces = YabaiMessage::query().spaces()?;
+    let space = find_space(&spaces, args.space.as_deref())?;
+    info!("Setting {} master windows for space {}", args.master_count.blue(), get_space_key(space).yellow());
+    *state.get_space_mut(space)? = args.master_count;
+
+    state.write_state()
+  }
+
+  pub(crate) fn reset_state(args: &StateResetArgs) -> color_eyre::Result<()> {
+    let mut state = State::read_state()?;
+    let spaces = YabaiMessage::query().spaces()?;
+    match &args.space {
+      Some(selector) => {
+        let space = find_space(&spaces, Some(selector))?;
+        info!("Resetting the state of space {}", get_space_key(space).yellow());
+        state.reset_space(space);
+      },
+      None => {
+        info!("Resetting the state of every space");
+        state.reset(&spaces);
+      },
     }
+
+    state.write_state()
+  }
+
+  pub(crate) fn gc_state() -> color_eyre::Result<()> {
+    let mut state = State::read_state()?;
+    let spaces = YabaiMessage::query().spaces()?;
+    let removed = state.gc(&spaces);
+    debug!("Removed spaces: {removed:?}");
+    for key in &removed {
+      println!("Removed space {key}");
+    }
+
+    state.write_state()
   }
-  pub(crate) fn close_focused_window() -> color_eyre::Result<()> { todo!() }
 }
 
 pub(crate) mod completion {
@@ -1,41 +1,317 @@
+use std::{
+  path::Path,
+  sync::atomic::{AtomicBool, Ordering},
+  time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::bail, owo_colors::OwoColorize};
 use fslock::LockFile;
+use log::{debug, error, info, trace, warn};
 
 use crate::{
-  task::create_initialized_windows_manager::{create_initialized_windows_manager, InitializedWindowsManager},
-  yabai::config::get_lockfile,
+  task::{
+    create_initialized_windows_manager::{
+      create_initialized_windows_manager, create_initialized_windows_manager_for, InitializedWindowsManager,
+    },
+    event_queue::{coalesce_events, drain_events, has_queued_events, queue_event, Event},
+    handlers::events::{
+      get_destroyed_window_spaces, get_space_by_id, get_space_by_index, get_visible_spaces, get_window_spaces,
+      on_yabai_start, retile_space, window_created, window_destroyed, window_focused, window_moved,
+    },
+  },
+  yabai::{
+    config::{get_config, get_event_queue_path, get_lockfile},
+    spaces::Space,
+  },
 };
 
-pub fn is_locked() -> color_eyre::Result<bool> {
-  let lockfile = get_lockfile()?;
-  let lockfile = LockFile::open(&lockfile)?;
-  Ok(lockfile.owns_lock())
+/// Delay between two attempts at taking the lock.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+static IS_LOCK_HELD: AtomicBool = AtomicBool::new(false);
+
+/// Whether the current process holds the lock.
+pub fn is_lock_held() -> bool { IS_LOCK_HELD.load(Ordering::SeqCst) }
+
+/// The lock, released when the guard is dropped, including on early returns.
+pub(crate) struct LockGuard {
+  lockfile: LockFile,
 }
 
-pub(super) fn run_locked<T>(f: T) -> color_eyre::Result<()>
+impl Drop for LockGuard {
+  fn drop(&mut self) {
+    IS_LOCK_HELD.store(false, Ordering::SeqCst);
+    if let Err(err) = self.lockfile.unlock() {
+      warn!("Unable to release the lock: {err}");
+    }
+    trace!("Lock released");
+  }
+}
+
+/// The pid written in the lockfile by the process holding the lock.
+fn get_lock_holder(path: &Path) -> Option<i32> { std::fs::read_to_string(path).ok()?.trim().parse().ok() }
+
+fn is_process_alive(pid: i32) -> bool {
+  // Signal 0 only checks that the process exists; EPERM means it exists but belongs to another user
+  let result = unsafe { libc::kill(pid, 0) };
+  result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Take the lock at `path` if no other process holds it.
+///
+/// The lock of a process that died is released by the system, unless a child inherited its file descriptor. Such a
+/// lock is never broken: the lockfile can't be unlinked without letting two processes hold the lock at once.
+pub(crate) fn try_acquire_lock_at(path: &Path) -> color_eyre::Result<Option<LockGuard>> {
+  let mut lockfile = LockFile::open(path)?;
+  if !lockfile.try_lock_with_pid()? {
+    return Ok(None);
+  }
+  IS_LOCK_HELD.store(true, Ordering::SeqCst);
+
+  Ok(Some(LockGuard { lockfile }))
+}
+
+/// Take the lock at `path`, waiting at most `timeout` for the process holding it.
+pub(crate) fn acquire_lock_at(path: &Path, timeout: Duration) -> color_eyre::Result<LockGuard> {
+  let start = Instant::now();
+  loop {
+    if let Some(guard) = try_acquire_lock_at(path)? {
+      debug!("Lock acquired after {elapsed:?}", elapsed = start.elapsed());
+      return Ok(guard);
+    }
+
+    if start.elapsed() >= timeout {
+      match get_lock_holder(path) {
+        Some(pid) if !is_process_alive(pid) => {
+          bail!(
+            "Timed out after {timeout:?} waiting for the lock held on behalf of dead process {pid}, a process it \
+             started may still hold it"
+          );
+        },
+        Some(pid) => {
+          bail!("Timed out after {timeout:?} waiting for the lock held by process {pid}");
+        },
+        None => {
+          bail!("Timed out after {timeout:?} waiting for the lock");
+        },
+      }
+    }
+    std::thread::sleep(LOCK_POLL_INTERVAL);
+  }
+}
+
+fn acquire_lock() -> color_eyre::Result<LockGuard> {
+  let config = get_config()?;
+  acquire_lock_at(&get_lockfile()?, Duration::from_millis(config.lock_timeout_ms))
+}
+
+/// Runs `f` on the focused space and persists the state it leaves. The lock must be held.
+fn run_with_state<T>(f: T) -> color_eyre::Result<()>
 where
-  T: FnOnce() -> color_eyre::Result<()>,
+  T: FnOnce(&mut InitializedWindowsManager) -> color_eyre::Result<()>,
 {
-  let lockfile = get_lockfile()?;
-  let mut lockfile = LockFile::open(&lockfile)?;
+  save_state_after(create_initialized_windows_manager()?, f)
+}
 
-  lockfile.lock_with_pid()?;
-  f()?;
-  lockfile.unlock()?;
+fn save_state_after<T>(mut result: InitializedWindowsManager, f: T) -> color_eyre::Result<()>
+where
+  T: FnOnce(&mut InitializedWindowsManager) -> color_eyre::Result<()>,
+{
+  let previous_state = serde_json::to_value(&result.state)?;
+  // Validated after the snapshot, so a clamped master count is persisted like any other change
+  result.wm.validate_state(&mut result.state)?;
+  f(&mut result)?;
+  result.state.set_window_order(&result.space, result.wm.window_order.clone());
+  result.state.set_insertion_point(&result.space, result.wm.insertion_point.clone());
+  // Polling and no-op events would otherwise rewrite the state, and its backup, many times a second
+  if serde_json::to_value(&result.state)? == previous_state {
+    trace!("State of space {} is unchanged", result.space.index.blue());
+    return Ok(());
+  }
+  result.state.write_state()?;
 
   Ok(())
 }
 
+fn handle_event(event: &Event) -> color_eyre::Result<()> {
+  trace!("Handling event {event:?}");
+  match *event {
+    Event::YabaiStarted => run_with_state(on_yabai_start),
+    // The focused space may not be the one of the window anymore
+    Event::WindowCreated { process_id, window_id, space } => {
+      run_on_spaces(get_space_by_index(space)?, |iwm| window_created(iwm, process_id, window_id))
+    },
+    Event::WindowMoved { space } => run_on_spaces(get_space_by_index(space)?, window_moved),
+    Event::WindowFocused { window_id } => run_with_state(|iwm| window_focused(iwm, window_id)),
+    Event::WindowDestroyed { window_id } => {
+      run_on_spaces(get_destroyed_window_spaces(window_id)?, |iwm| window_destroyed(iwm, window_id))
+    },
+    Event::WindowMinimized { window_id }
+    | Event::WindowDeminimized { window_id }
+    | Event::WindowResized { window_id } => run_on_spaces(get_window_spaces(window_id)?, retile_space),
+    // Hiding an application can empty slots of every space on screen
+    Event::ApplicationHidden { .. } | Event::ApplicationVisible { .. } => {
+      run_on_spaces(get_visible_spaces()?, retile_space)
+    },
+    // Windows of the space may have changed while it wasn't visible
+    Event::SpaceChanged { space_id } => run_on_spaces(get_space_by_id(space_id)?, retile_space),
+    Event::DisplayAdded { .. } | Event::DisplayRemoved { .. } | Event::DisplayChanged { .. } => {
+      run_on_spaces(get_visible_spaces()?, retile_space)
+    },
+  }
+}
+
+/// Handles the queued events, then releases the lock.
+///
+/// Events queued by processes that gave up on the lock just before it was released are handled as well.
+fn drain_and_release(mut guard: LockGuard) -> color_eyre::Result<()> {
+  let lockfile = get_lockfile()?;
+  let queue = get_event_queue_path()?;
+  loop {
+    while has_queued_events(&queue) {
+      let events = coalesce_events(drain_events(&queue)?);
+      info!("Handling {len} queued events", len = events.len());
+      for event in &events {
+        // A failing event must not prevent the following ones from being handled
+        if let Err(err) = handle_event(event) {
+          error!("Unable to handle event {event:?}: {err}");
+        }
+      }
+    }
+    drop(guard);
+
+    if !has_queued_events(&queue) {
+      return Ok(());
+    }
+    match try_acquire_lock_at(&lockfile)? {
+      Some(next_guard) => guard = next_guard,
+      // The new holder drains the queue
+      None => return Ok(()),
+    }
+  }
+}
+
+pub(super) fn run_locked<T>(f: T) -> color_eyre::Result<()>
+where
+  T: FnOnce() -> color_eyre::Result<()>,
+{
+  let guard = acquire_lock()?;
+  let result = f();
+  drain_and_release(guard)?;
+  result
+}
+
 pub(super) fn run_locked_with_state<T>(f: T) -> color_eyre::Result<()>
 where
   T: FnOnce(&mut InitializedWindowsManager) -> color_eyre::Result<()>,
 {
-  let lockfile = get_lockfile()?;
-  let mut lockfile = LockFile::open(&lockfile)?;
+  let guard = acquire_lock()?;
+  let result = run_with_state(f);
+  drain_and_release(guard)?;
+  result
+}
 
-  lockfile.lock_with_pid()?;
-  let mut result = create_initialized_windows_manager()?;
-  f(&mut result)?;
-  lockfile.unlock()?;
+/// Runs `f` on each of `spaces`, which don't have to be focused, under a single hold of the lock.
+pub(super) fn run_locked_on_spaces<T>(spaces: Vec<Space>, f: T) -> color_eyre::Result<()>
+where
+  T: FnMut(&mut InitializedWindowsManager) -> color_eyre::Result<()>,
+{
+  let guard = acquire_lock()?;
+  let result = run_on_spaces(spaces, f);
+  drain_and_release(guard)?;
+  result
+}
+
+/// Runs `f` on each of `spaces` and persists the state it leaves. The lock must be held.
+fn run_on_spaces<T>(spaces: Vec<Space>, mut f: T) -> color_eyre::Result<()>
+where
+  T: FnMut(&mut InitializedWindowsManager) -> color_eyre::Result<()>,
+{
+  for space in spaces {
+    trace!("Running on space {}", space.index.blue());
+    save_state_after(create_initialized_windows_manager_for(space)?, &mut f)?;
+  }
 
   Ok(())
 }
+
+/// Queues `event`, then handles the queue unless another process holds the lock and will handle it.
+pub(super) fn run_event(event: Event) -> color_eyre::Result<()> {
+  queue_event(&get_event_queue_path()?, &event)?;
+  // Trying after queueing, the holder may have released the lock without seeing the event
+  match try_acquire_lock_at(&get_lockfile()?)? {
+    Some(guard) => drain_and_release(guard),
+    None => {
+      debug!("Lock is held, {event:?} is left to its holder");
+      Ok(())
+    },
+  }
+}
+
+/// Prints whether the lock is free, or which process holds it.
+pub(crate) fn print_lock_status() -> color_eyre::Result<()> {
+  let path = get_lockfile()?;
+  let mut lockfile = LockFile::open(&path)?;
+  if lockfile.try_lock()? {
+    lockfile.unlock()?;
+    println!("Lock is {}", "free".green());
+    return Ok(());
+  }
+
+  match get_lock_holder(&path) {
+    Some(pid) if is_process_alive(pid) => println!("Lock is held by process {}", pid.yellow()),
+    Some(pid) => println!("Lock is held by process {}, which is {}", pid.yellow(), "dead".red()),
+    None => println!("Lock is held by an unknown process"),
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::test_dir::TestDir;
+
+  #[test_log::test]
+  fn guard_releases_the_lock() {
+    let dir = TestDir::new("lock-guard");
+    let path = dir.path("ymsp.lock");
+    {
+      let _guard = acquire_lock_at(&path, Duration::ZERO).unwrap();
+      assert_eq!(get_lock_holder(&path), Some(std::process::id() as i32));
+      assert!(!LockFile::open(&path).unwrap().try_lock().unwrap());
+    }
+
+    assert!(LockFile::open(&path).unwrap().try_lock().unwrap());
+  }
+
+  #[test_log::test]
+  fn lock_times_out_when_held() {
+    let dir = TestDir::new("lock-timeout");
+    let path = dir.path("ymsp.lock");
+    let mut holder = LockFile::open(&path).unwrap();
+    holder.lock_with_pid().unwrap();
+
+    let result = acquire_lock_at(&path, Duration::from_millis(30));
+    assert!(result.is_err_and(|err| err.to_string().starts_with("Timed out")));
+    holder.unlock().unwrap();
+  }
+
+  #[test_log::test]
+  fn lock_held_on_behalf_of_dead_process_is_not_taken() {
+    let dir = TestDir::new("lock-dead");
+    let path = dir.path("ymsp.lock");
+    let mut holder = LockFile::open(&path).unwrap();
+    holder.lock().unwrap();
+    // A pid that can't exist, standing for a process that died while a child kept its lock
+    std::fs::write(&path, i32::MAX.to_string()).unwrap();
+
+    assert!(try_acquire_lock_at(&path).unwrap().is_none());
+    let result = acquire_lock_at(&path, Duration::ZERO);
+    assert!(result.is_err_and(|err| err.to_string().contains("dead process")));
+    assert!(path.exists());
+    holder.unlock().unwrap();
+  }
+}
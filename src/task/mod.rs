@@ -7,23 +7,41 @@ use log::info;
 
 use crate::{
   task::{
+    daemon::{run_daemon, send},
+    debounce::run_debounced_event,
+    event_queue::{get_yabai_env_id, get_yabai_window_space, Event},
     handlers::{
-      events::{on_yabai_start, window_created, window_moved},
-      focus::{focus_down_window, focus_master_window, focus_next_display, focus_previous_display, focus_up_window},
-      move_window::{
-        close_focused_window, move_window_to_master, move_window_to_next_display, move_window_to_previous_display,
+      close::close_focused_window,
+      focus::{
+        focus_down_window, focus_last, focus_last_master, focus_last_stack, focus_master, focus_master_window,
+        focus_next_display, focus_next_in_stack, focus_prev_in_stack, focus_previous_display, focus_stack,
+        focus_stack_bottom, focus_stack_top, focus_up_window,
       },
+      layout::{cycle_layout, set_layout},
+      master_ratio::{grow_master, set_master_ratio, shrink_master},
+      monocle::toggle_monocle,
+      move_window::{move_window_to_master, move_window_to_next_display, move_window_to_previous_display},
+      rotate::{rotate_next, rotate_prev},
+      state::{gc_state, reset_state, set_state, show_state},
+      swap::{swap_down, swap_up},
       window_count::{decrease_master_window_count, increase_master_window_count},
+      zoom::zoom,
     },
-    lock::run_locked_with_state,
+    lock::{print_lock_status, run_locked, run_locked_with_state},
+    watch::watch,
     ymsp_task::YmspTask,
   },
+  window_manager::layouts::Layout,
   yabai::config::initialize_config,
 };
 
 mod create_initialized_windows_manager;
+pub(crate) mod daemon;
+pub(crate) mod debounce;
+pub(crate) mod event_queue;
 pub(crate) mod handlers;
 pub(crate) mod lock;
+pub(crate) mod watch;
 pub(crate) mod ymsp_task;
 
 #[derive(Args, Debug, Eq, PartialEq)]
@@ -32,7 +50,108 @@ pub struct CompletionArgs {
   pub shell: Shell,
 }
 
+fn parse_master_ratio(value: &str) -> Result<f64, String> {
+  let ratio: f64 = value.parse().map_err(|e| format!("{value} is not a number: {e}"))?;
+  if ratio > 0f64 && ratio < 1f64 {
+    Ok(ratio)
+  } else {
+    Err(format!("{ratio} is not between 0 and 1"))
+  }
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct MasterRatioArgs {
+  /// The share of the display taken by the master pane, between 0 and 1
+  #[arg(value_parser = parse_master_ratio)]
+  pub ratio: f64,
+}
+
+fn parse_positive_number(value: &str) -> Result<usize, String> {
+  let number: usize = value.parse().map_err(|e| format!("{value} is not a positive number: {e}"))?;
+  if number > 0 {
+    Ok(number)
+  } else {
+    Err(format!("{number} is not a positive number"))
+  }
+}
+
+#[derive(Args, Debug, Eq, PartialEq)]
+pub struct SlotArgs {
+  /// The position of the window in its pane, starting at 1 for the top window
+  #[arg(value_parser = parse_positive_number)]
+  pub slot: usize,
+}
+
+#[derive(Args, Debug, Eq, PartialEq)]
+pub struct LayoutArgs {
+  /// The layout used to tile the windows of the focused space
+  #[arg(value_enum)]
+  pub layout: Layout,
+}
+
+#[derive(Args, Debug, Eq, PartialEq)]
+pub struct StateShowArgs {
+  /// Print the raw state as JSON instead of a table
+  #[arg(long)]
+  pub json: bool,
+}
+
+#[derive(Args, Debug, Eq, PartialEq)]
+pub struct StateSetArgs {
+  /// The mission-control index or the label of the space, the focused space by default
+  #[arg(long)]
+  pub space: Option<String>,
+  /// The number of master windows of the space
+  #[arg(long, value_parser = parse_positive_number)]
+  pub master_count: usize,
+}
+
+#[derive(Args, Debug, Eq, PartialEq)]
+pub struct StateResetArgs {
+  /// The mission-control index or the label of the space, every space by default
+  #[arg(long)]
+  pub space: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Eq, PartialEq)]
+pub enum StateCommand {
+  /// Prints the state of every space
+  Show(StateShowArgs),
+  /// Sets the number of master windows of a space
+  Set(StateSetArgs),
+  /// Forgets the state of a space, or of every space
+  Reset(StateResetArgs),
+  /// Forgets the spaces that yabai no longer reports
+  Gc,
+}
+
+#[derive(Args, Debug, Eq, PartialEq)]
+pub struct SendArgs {
+  /// The task to run, followed by its arguments
+  #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+  pub task: Vec<String>,
+}
+
+#[derive(Args, Debug, Eq, PartialEq)]
+pub struct WatchArgs {
+  /// The delay between two polls, in milliseconds
+  #[arg(long, default_value_t = 500)]
+  pub interval: u64,
+  /// The number of polls a drifted layout must stay unchanged for before it is re-tiled
+  #[arg(long, default_value_t = 3, value_parser = parse_positive_number)]
+  pub stable_ticks: usize,
+  /// Polls every visible space rather than only the focused one
+  #[arg(long)]
+  pub all_visible: bool,
+}
+
 #[derive(Subcommand, Debug, Eq, PartialEq)]
+pub enum LockCommand {
+  /// Prints whether the lock is free, or which process holds it
+  Status,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
 pub enum Task {
   /// Generate shell completion scripts
   #[clap(value_enum)]
@@ -43,6 +162,28 @@ pub enum Task {
   WindowCreated,
   /// Event handler for when a window is moved
   WindowMoved,
+  /// Event handler for when a window is focused
+  WindowFocused,
+  /// Event handler for when a window is destroyed
+  WindowDestroyed,
+  /// Event handler for when a window is minimized
+  WindowMinimized,
+  /// Event handler for when a window is restored from the dock
+  WindowDeminimized,
+  /// Event handler for when a window is resized
+  WindowResized,
+  /// Event handler for when an application is hidden
+  ApplicationHidden,
+  /// Event handler for when a hidden application is shown again
+  ApplicationVisible,
+  /// Event handler for when the focused space changes
+  SpaceChanged,
+  /// Event handler for when a display is connected
+  DisplayAdded,
+  /// Event handler for when a display is disconnected
+  DisplayRemoved,
+  /// Event handler for when the focused display changes
+  DisplayChanged,
   /// Focuses on the window above the currently focused window
   FocusDownWindow,
   /// Focuses on the window below the currently focused window
@@ -51,6 +192,28 @@ pub enum Task {
   IncreaseMasterWindowCount,
   /// Decreases the number of master windows.
   DecreaseMasterWindowCount,
+  /// Increases the share of the display taken by the master pane.
+  GrowMaster,
+  /// Decreases the share of the display taken by the master pane.
+  ShrinkMaster,
+  /// Sets the share of the display taken by the master pane.
+  SetMasterRatio(MasterRatioArgs),
+  /// Sets the layout of the focused space.
+  SetLayout(LayoutArgs),
+  /// Switches the focused space to the next layout.
+  CycleLayout,
+  /// Zooms the focused window, or restores the windows as they were before the zoom.
+  ToggleMonocle,
+  /// Shifts every window one slot towards the master pane, the first window going to the bottom of the stack.
+  RotateNext,
+  /// Shifts every window one slot away from the master pane, the bottom stack window becoming master.
+  RotatePrev,
+  /// Moves the focused window one slot up, across panes at the top of a pane.
+  SwapUp,
+  /// Moves the focused window one slot down, across panes at the bottom of a pane.
+  SwapDown,
+  /// Swaps the focused window with the master window, or the master window with the top stack window.
+  Zoom,
   /// Quits the currently focused window by Yabai.
   CloseFocusedWindow,
   /// Focus the next display
@@ -65,24 +228,121 @@ pub enum Task {
   MoveToMaster,
   /// Focus the master window
   FocusMasterWindow,
+  /// Focus the previously focused window of the focused space
+  FocusLast,
+  /// Focus the most recently focused master window
+  FocusLastMaster,
+  /// Focus the most recently focused stack window
+  FocusLastStack,
+  /// Focus the master window in the given slot
+  FocusMaster(SlotArgs),
+  /// Focus the stack window in the given slot
+  FocusStack(SlotArgs),
+  /// Focus the top stack window
+  FocusStackTop,
+  /// Focus the bottom stack window
+  FocusStackBottom,
+  /// Focus the next window in the stack of the focused window
+  FocusNextInStack,
+  /// Focus the previous window in the stack of the focused window
+  FocusPrevInStack,
+  /// Inspect or fix the persisted state
+  #[command(subcommand)]
+  State(StateCommand),
+  /// Inspect the lock serializing ymsp processes
+  #[command(subcommand)]
+  Lock(LockCommand),
+  /// Keeps running, handling the tasks sent by `ymsp send` over a Unix socket
+  Daemon,
+  /// Runs a task in the daemon, or in the current process when no daemon is running
+  Send(SendArgs),
+  /// Polls the spaces and re-tiles those whose layout drifted, for setups where yabai signals can't be used
+  Watch(WatchArgs),
 }
 
 impl std::fmt::Display for Task {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "{:?}", self) }
 }
 
+impl Task {
+  /// The yabai signal handled by the task, with the ids yabai passed in the environment.
+  pub(crate) fn to_event(&self) -> color_eyre::Result<Option<Event>> {
+    let event = match self {
+      Task::OnYabaiStart => Event::YabaiStarted,
+      Task::WindowCreated => {
+        Event::WindowCreated {
+          process_id: get_yabai_env_id("YABAI_PROCESS_ID")?,
+          window_id: get_yabai_env_id("YABAI_WINDOW_ID")?,
+          space: get_yabai_window_space()?,
+        }
+      },
+      Task::WindowMoved => Event::WindowMoved { space: get_yabai_window_space()? },
+      Task::WindowFocused => Event::WindowFocused { window_id: get_yabai_env_id("YABAI_WINDOW_ID")? },
+      Task::WindowDestroyed => Event::WindowDestroyed { window_id: get_yabai_env_id("YABAI_WINDOW_ID")? },
+      Task::WindowMinimized => Event::WindowMinimized { window_id: get_yabai_env_id("YABAI_WINDOW_ID")? },
+      Task::WindowDeminimized => Event::WindowDeminimized { window_id: get_yabai_env_id("YABAI_WINDOW_ID")? },
+      Task::WindowResized => Event::WindowResized { window_id: get_yabai_env_id("YABAI_WINDOW_ID")? },
+      Task::ApplicationHidden => Event::ApplicationHidden { process_id: get_yabai_env_id("YABAI_PROCESS_ID")? },
+      Task::ApplicationVisible => Event::ApplicationVisible { process_id: get_yabai_env_id("YABAI_PROCESS_ID")? },
+      Task::SpaceChanged => Event::SpaceChanged { space_id: get_yabai_env_id("YABAI_SPACE_ID")? },
+      Task::DisplayAdded => Event::DisplayAdded { display_id: get_yabai_env_id("YABAI_DISPLAY_ID")? },
+      Task::DisplayRemoved => Event::DisplayRemoved { display_id: get_yabai_env_id("YABAI_DISPLAY_ID")? },
+      Task::DisplayChanged => Event::DisplayChanged { display_id: get_yabai_env_id("YABAI_DISPLAY_ID")? },
+      _ => return Ok(None),
+    };
+
+    Ok(Some(event))
+  }
+
+  /// Whether the daemon can run the task; tasks printing to the terminal must run in the calling process.
+  pub(crate) fn is_forwardable(&self) -> bool {
+    !matches!(
+      self,
+      Task::Completions(_) | Task::State(_) | Task::Lock(_) | Task::Daemon | Task::Send(_) | Task::Watch(_)
+    )
+  }
+}
+
 impl YmspTask for Task {
   fn run(&self) -> color_eyre::Result<()> {
     info!("Running task {}", self.yellow());
 
     initialize_config()?;
+    if let Some(event) = self.to_event()? {
+      return run_debounced_event(event);
+    }
     match self {
-      Task::OnYabaiStart => run_locked_with_state(on_yabai_start),
-      Task::WindowCreated => run_locked_with_state(window_created),
-      Task::WindowMoved => run_locked_with_state(window_moved),
       Task::IncreaseMasterWindowCount => run_locked_with_state(increase_master_window_count),
       Task::DecreaseMasterWindowCount => run_locked_with_state(decrease_master_window_count),
+      Task::GrowMaster => run_locked_with_state(grow_master),
+      Task::ShrinkMaster => run_locked_with_state(shrink_master),
+      Task::SetMasterRatio(args) => run_locked_with_state(|iwm| set_master_ratio(iwm, args.ratio)),
+      Task::SetLayout(args) => run_locked_with_state(|iwm| set_layout(iwm, args.layout)),
+      Task::CycleLayout => run_locked_with_state(cycle_layout),
+      Task::ToggleMonocle => run_locked_with_state(toggle_monocle),
+      Task::RotateNext => run_locked_with_state(rotate_next),
+      Task::RotatePrev => run_locked_with_state(rotate_prev),
+      Task::SwapUp => run_locked_with_state(swap_up),
+      Task::SwapDown => run_locked_with_state(swap_down),
+      Task::Zoom => run_locked_with_state(zoom),
       Task::FocusMasterWindow => focus_master_window(),
+      Task::FocusLast => run_locked_with_state(focus_last),
+      Task::FocusLastMaster => run_locked_with_state(focus_last_master),
+      Task::FocusLastStack => run_locked_with_state(focus_last_stack),
+      Task::FocusMaster(args) => run_locked_with_state(|iwm| focus_master(iwm, args.slot)),
+      Task::FocusStack(args) => run_locked_with_state(|iwm| focus_stack(iwm, args.slot)),
+      Task::FocusStackTop => run_locked_with_state(focus_stack_top),
+      Task::FocusStackBottom => run_locked_with_state(focus_stack_bottom),
+      Task::FocusNextInStack => focus_next_in_stack(),
+      Task::FocusPrevInStack => focus_prev_in_stack(),
+      Task::State(StateCommand::Show(args)) => run_locked(|| show_state(args)),
+      Task::State(StateCommand::Set(args)) => run_locked(|| set_state(args)),
+      Task::State(StateCommand::Reset(args)) => run_locked(|| reset_state(args)),
+      Task::State(StateCommand::Gc) => run_locked(gc_state),
+      Task::Lock(LockCommand::Status) => print_lock_status(),
+      Task::Daemon => run_daemon(),
+      Task::Send(args) => send(&args.task),
+      Task::Watch(args) => watch(args),
       Task::FocusUpWindow => run_locked_with_state(focus_up_window),
       Task::FocusDownWindow => run_locked_with_state(focus_down_window),
       Task::FocusNextDisplay => focus_next_display(),
@@ -90,7 +350,7 @@ impl YmspTask for Task {
       Task::MoveToMaster => move_window_to_master(),
       Task::MoveToNextDisplay => move_window_to_next_display(),
       Task::MoveToPreviousDisplay => move_window_to_previous_display(),
-      Task::CloseFocusedWindow => close_focused_window(),
+      Task::CloseFocusedWindow => run_locked_with_state(close_focused_window),
       action => unreachable!("{:?} must not be called", action.red().bold()),
     }
   }
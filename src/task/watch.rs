@@ -0,0 +1,194 @@
+use std::{collections::HashMap, time::Duration};
+
+use color_eyre::owo_colors::OwoColorize;
+use log::{debug, info, trace, warn};
+
+use crate::{
+  task::{
+    create_initialized_windows_manager::InitializedWindowsManager, handlers::events::get_visible_spaces,
+    lock::run_locked_on_spaces, WatchArgs,
+  },
+  window_manager::layout_visibility::LayoutValidity,
+  yabai::{command::message::YabaiMessage, spaces::Space, state::get_space_key, window::WindowId},
+};
+
+/// Polling slows down to at most `2^MAX_BACKOFF` intervals while a window is dragged.
+const MAX_BACKOFF: u32 = 3;
+
+/// Position of a window, compared between ticks to tell whether the windows settled.
+type WindowFrame = (WindowId, f64, f64, f64, f64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Observation {
+  /// The layout is valid, or the space isn't tiled by ymsp
+  Valid,
+  /// A window is being dragged
+  Grabbed,
+  /// The layout is invalid, with the windows in these positions
+  Invalid(Vec<WindowFrame>),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Action {
+  Wait,
+  Retile,
+}
+
+/// Tracks for how many ticks the layout of each space stayed invalid with its windows unchanged.
+#[derive(Debug, Default)]
+pub(crate) struct DriftTracker {
+  stable_ticks: usize,
+  drifts: HashMap<String, (Vec<WindowFrame>, usize)>,
+  backoff: u32,
+  is_grabbed: bool,
+}
+
+impl DriftTracker {
+  pub(crate) fn new(stable_ticks: usize) -> Self { Self { stable_ticks, ..Default::default() } }
+
+  /// Records what a tick saw on the space with `key`, telling whether the space should be re-tiled.
+  pub(crate) fn observe(&mut self, key: &str, observation: Observation) -> Action {
+    match observation {
+      Observation::Valid => {
+        self.drifts.remove(key);
+        Action::Wait
+      },
+      Observation::Grabbed => {
+        // Re-tiling in the middle of a drag would fight the user
+        self.is_grabbed = true;
+        self.drifts.remove(key);
+        Action::Wait
+      },
+      Observation::Invalid(frames) => {
+        let ticks = match self.drifts.get(key) {
+          Some((previous_frames, ticks)) if *previous_frames == frames => ticks + 1,
+          _ => 1,
+        };
+        if ticks >= self.stable_ticks {
+          self.drifts.remove(key);
+          Action::Retile
+        } else {
+          self.drifts.insert(key.to_string(), (frames, ticks));
+          Action::Wait
+        }
+      },
+    }
+  }
+
+  /// The delay before the next tick, growing while windows are dragged.
+  pub(crate) fn end_tick(&mut self, interval: Duration) -> Duration {
+    self.backoff = if self.is_grabbed { (self.backoff + 1).min(MAX_BACKOFF) } else { 0 };
+    self.is_grabbed = false;
+    interval * 2u32.pow(self.backoff)
+  }
+}
+
+fn observe_space(iwm: &InitializedWindowsManager) -> color_eyre::Result<Observation> {
+  let (wm, state, space) = (&iwm.wm, &iwm.state, &iwm.space);
+  if state.get_monocle(space).is_some() {
+    return Ok(Observation::Valid);
+  }
+  if wm.windows.iter().any(|window| window.is_grabbed) {
+    return Ok(Observation::Grabbed);
+  }
+
+  match wm.is_valid_layout(None)? {
+    LayoutValidity::Valid => Ok(Observation::Valid),
+    LayoutValidity::Invalid(reason) => {
+      trace!("Layout of space {} is invalid: {reason}", space.index.blue());
+      let frames = wm
+        .windows
+        .iter()
+        .map(|window| (window.id, window.frame.x, window.frame.y, window.frame.w, window.frame.h))
+        .collect();
+      Ok(Observation::Invalid(frames))
+    },
+  }
+}
+
+fn get_watched_spaces(args: &WatchArgs) -> color_eyre::Result<Vec<Space>> {
+  if !args.all_visible {
+    return Ok(vec![YabaiMessage::query().current_space()?]);
+  }
+
+  get_visible_spaces()
+}
+
+/// Polls the watched spaces until the process is killed, re-tiling those whose layout drifted.
+pub(crate) fn watch(args: &WatchArgs) -> color_eyre::Result<()> {
+  let interval = Duration::from_millis(args.interval);
+  let mut tracker = DriftTracker::new(args.stable_ticks);
+  info!("Watching spaces every {interval:?}");
+
+  loop {
+    let result = get_watched_spaces(args).and_then(|spaces| {
+      run_locked_on_spaces(spaces, |iwm| {
+        let observation = observe_space(iwm)?;
+        if tracker.observe(&get_space_key(&iwm.space), observation) == Action::Retile {
+          info!("Layout of space {} drifted, updating windows", iwm.space.index.blue());
+          let space_state = *iwm.state.get_space(&iwm.space)?;
+          iwm.wm.update_windows(space_state)?;
+        }
+        Ok(())
+      })
+    });
+    // yabai may be restarting or the lock held for too long, the next tick tries again
+    if let Err(err) = result {
+      warn!("Unable to watch spaces: {err}");
+    }
+
+    let delay = tracker.end_tick(interval);
+    debug!("Next tick in {delay:?}");
+    std::thread::sleep(delay);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  fn invalid(x: f64) -> Observation { Observation::Invalid(vec![(1, x, 0.0, 100.0, 100.0)]) }
+
+  #[test]
+  fn retiles_after_stable_ticks() {
+    let mut tracker = DriftTracker::new(3);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Wait);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Wait);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Retile);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Wait);
+  }
+
+  #[test]
+  fn moving_windows_restart_the_count() {
+    let mut tracker = DriftTracker::new(2);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Wait);
+    assert_eq!(tracker.observe("code", invalid(10.0)), Action::Wait);
+    assert_eq!(tracker.observe("web", invalid(10.0)), Action::Wait);
+    assert_eq!(tracker.observe("code", invalid(10.0)), Action::Retile);
+  }
+
+  #[test]
+  fn valid_or_grabbed_layouts_reset_the_count() {
+    let mut tracker = DriftTracker::new(2);
+    tracker.observe("code", invalid(0.0));
+    tracker.observe("code", Observation::Valid);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Wait);
+    tracker.observe("code", Observation::Grabbed);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Wait);
+    assert_eq!(tracker.observe("code", invalid(0.0)), Action::Retile);
+  }
+
+  #[test]
+  fn backs_off_while_grabbed() {
+    let interval = Duration::from_millis(100);
+    let mut tracker = DriftTracker::new(2);
+    assert_eq!(tracker.end_tick(interval), interval);
+    for expected in [200, 400, 800, 800] {
+      tracker.observe("code", Observation::Grabbed);
+      assert_eq!(tracker.end_tick(interval), Duration::from_millis(expected));
+    }
+    assert_eq!(tracker.end_tick(interval), interval);
+  }
+}
@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+/// Directory holding the files of a test, removed when dropped, even when the test fails.
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+  /// Creates an empty directory, `name` must be unique among the tests.
+  pub(crate) fn new(name: &str) -> Self {
+    let path = std::env::temp_dir().join(format!("ymsp-{name}-{pid}", pid = std::process::id()));
+    // Left behind by a test run that was killed
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    Self(path)
+  }
+
+  /// Path of the file `name` in the directory.
+  pub(crate) fn path(&self, name: &str) -> PathBuf { self.0.join(name) }
+}
+
+impl Drop for TestDir {
+  // Panicking again while a failed test unwinds would abort the whole test run
+  fn drop(&mut self) { let _ = std::fs::remove_dir_all(&self.0); }
+}
@@ -0,0 +1,55 @@
+use log::{debug, info, trace};
+
+use crate::{
+  window_manager::WindowsManager,
+  yabai::{
+    command::{direction_selector::YabaiDirectionSelector, message::YabaiMessage},
+    window::Window,
+  },
+};
+
+type Result<T> = color_eyre::Result<T>;
+
+impl WindowsManager {
+  /// Re-insert `window` next to `target`, in the given direction.
+  pub(crate) fn warp_window_next_to(
+    &self, window: &Window, target: &Window, direction: &YabaiDirectionSelector,
+  ) -> Result<()> {
+    if window.id == target.id {
+      return Ok(());
+    }
+
+    trace!("Warping {window} {direction:?} of {target}");
+    let message = YabaiMessage::window(target).insert(direction.clone())?;
+    self.send_yabai_message(message)?;
+    let message = YabaiMessage::window(window).warp(target)?;
+    self.send_yabai_message(message)
+  }
+
+  /// Rebuild the layout of the space from scratch.
+  ///
+  /// Each group is placed after the previous one in `group_direction`, and the windows of a group are placed after
+  /// each other in `window_direction`. The split ratios are balanced afterwards.
+  pub(crate) fn arrange_windows(
+    &self, groups: &[Vec<Window>], group_direction: YabaiDirectionSelector, window_direction: YabaiDirectionSelector,
+  ) -> Result<()> {
+    let groups = groups.iter().filter(|group| !group.is_empty()).collect::<Vec<_>>();
+    info!("Arranging {len} groups of windows", len = groups.len());
+
+    // The first window of each group is chained to the first window of the previous group, so every group ends up
+    // in its own subtree, then the remaining windows are chained inside their group.
+    let heads = groups.iter().filter_map(|group| group.first()).collect::<Vec<_>>();
+    for (previous, head) in heads.iter().zip(heads.iter().skip(1)) {
+      self.warp_window_next_to(head, previous, &group_direction)?;
+    }
+    for group in &groups {
+      for (previous, window) in group.iter().zip(group.iter().skip(1)) {
+        self.warp_window_next_to(window, previous, &window_direction)?;
+      }
+    }
+
+    debug!("Balancing the space");
+    let message = YabaiMessage::space(self.space.index).balance()?;
+    self.send_yabai_message(message)
+  }
+}
@@ -15,6 +15,10 @@ impl WindowsManager {
     for window in windows {
       let window = self.get_updated_window_data(&window);
       if let Some(window) = window {
+        if window.stack_index > 0 {
+          debug!("Skipped columnizing stacked window {window}");
+          continue;
+        }
         if window.split_type == split_type {
           let message = YabaiMessage::window(window).toggle(YabaiToggleSelector::Split)?;
           self.send_yabai_message(message)?;
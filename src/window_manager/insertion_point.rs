@@ -0,0 +1,42 @@
+use log::{debug, trace};
+
+use crate::{window_manager::WindowsManager, yabai::command::message::YabaiMessage};
+
+type Result<T> = color_eyre::Result<T>;
+
+impl WindowsManager {
+  /// Point yabai at the slot where the layout expects the next created window, so it doesn't have to be moved.
+  pub(crate) fn update_insertion_point(&mut self) -> Result<()> {
+    // Windows re-inserted in the tree since the insertion point was set may have used it up
+    if self.has_inserted_windows.replace(false) {
+      trace!("Insertion point {:?} may have been used", self.insertion_point);
+      self.insertion_point = None;
+    }
+
+    let insertion_point = self.layout.engine().get_insertion_point(self)?;
+    if insertion_point == self.insertion_point {
+      trace!("Insertion point {insertion_point:?} is already set");
+      return Ok(());
+    }
+
+    match (&insertion_point, &self.insertion_point) {
+      (Some(insertion_point), _) => {
+        debug!("Setting insertion point {insertion_point:?}");
+        let message = YabaiMessage::window(insertion_point.window).insert(insertion_point.direction.clone())?;
+        self.send_yabai_message(message)?;
+      },
+      (None, Some(previous_insertion_point)) => {
+        // Inserting in the same direction again clears the insertion point
+        debug!("Clearing insertion point {previous_insertion_point:?}");
+        let message =
+          YabaiMessage::window(previous_insertion_point.window).insert(previous_insertion_point.direction.clone())?;
+        self.send_yabai_message(message)?;
+      },
+      (None, None) => {},
+    }
+    self.has_inserted_windows.set(false);
+    self.insertion_point = insertion_point;
+
+    Ok(())
+  }
+}
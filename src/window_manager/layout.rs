@@ -9,13 +9,12 @@ use crate::{
 type Result<T> = color_eyre::Result<T>;
 
 impl WindowsManager {
-  pub(crate) fn is_valid_layout(&self, target_num_master_windows: Option<usize>) -> Result<LayoutValidity> {
+  pub(crate) fn is_valid_tall_layout(&self, target_num_master_windows: usize) -> Result<LayoutValidity> {
     info!("Starting valid layout check...");
     if self.windows.is_empty() {
       info!("Layout is valid");
       return Ok(LayoutValidity::Valid);
     }
-    let target_num_master_windows = target_num_master_windows.unwrap_or(self.expected_current_num_master_windows);
     debug!("Target number of master windows: {}", target_num_master_windows.blue());
 
     if target_num_master_windows > self.windows.len()
@@ -46,6 +45,35 @@ impl WindowsManager {
         }
       }
 
+      if self.is_deck_mode()? {
+        let stack_windows = self.get_stack_windows();
+        if stack_windows.len() > 1 {
+          if let Some(window) = stack_windows.iter().find(|window| window.stack_index == 0) {
+            let reason = format!("A stack window ({window}) is not stacked.");
+            error!("Layout invalid: {}", reason.red());
+            return Ok(LayoutValidity::Invalid(reason));
+          }
+        }
+      }
+
+      if let Some(max_stack_rows) = self.get_max_stack_rows()? {
+        let stack_columns = self.get_stack_columns()?;
+        let num_stack_columns = self.get_num_stack_columns(target_num_master_windows)?;
+        if stack_columns.len() != num_stack_columns {
+          let reason = format!(
+            "Number of stack columns does not equal expected number of stack columns ({}/{num_stack_columns})",
+            stack_columns.len()
+          );
+          error!("Layout invalid: {}", reason.red());
+          return Ok(LayoutValidity::Invalid(reason));
+        }
+        if let Some(column) = stack_columns.iter().find(|column| column.len() > max_stack_rows) {
+          let reason = format!("A stack column has more than {max_stack_rows} windows ({len})", len = column.len());
+          error!("Layout invalid: {}", reason.red());
+          return Ok(LayoutValidity::Invalid(reason));
+        }
+      }
+
       info!("Layout is valid");
       Ok(LayoutValidity::Valid)
     }
@@ -53,6 +81,10 @@ impl WindowsManager {
 
   pub(crate) fn get_dividing_line_x_coordinate(&self) -> Result<f64> {
     trace!("get_dividing_line_x_coordinate() called.");
+    if self.get_max_stack_rows()?.is_some() {
+      return self.get_column_dividing_line_x_coordinate();
+    }
+
     let config = get_config()?;
     trace!("Master position: {master_position:?}", master_position = config.master_position);
     match config.master_position {
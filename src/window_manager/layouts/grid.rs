@@ -0,0 +1,88 @@
+use color_eyre::owo_colors::OwoColorize;
+use log::{error, info, trace};
+
+use crate::{
+  window_manager::{
+    layout_visibility::LayoutValidity, layouts::LayoutEngine, window_position::compare_position, WindowsManager,
+  },
+  yabai::{command::direction_selector::YabaiDirectionSelector, window::Window},
+};
+
+type Result<T> = color_eyre::Result<T>;
+
+/// Every window in an evenly sized grid, filled row by row.
+pub(super) struct GridLayout;
+
+impl GridLayout {
+  /// Number of columns of a grid holding `len` windows.
+  fn get_num_columns(len: usize) -> usize { (len as f64).sqrt().ceil() as usize }
+
+  /// Groups the windows by row, from top to bottom.
+  fn get_rows(wm: &WindowsManager) -> Vec<Vec<Window>> {
+    let mut windows = wm.windows.clone();
+    windows.sort_by(compare_position);
+    let mut rows: Vec<Vec<Window>> = vec![];
+    for window in windows {
+      match rows.last_mut() {
+        Some(row) if row.first().is_some_and(|first| first.frame.y == window.frame.y) => row.push(window),
+        _ => rows.push(vec![window]),
+      }
+    }
+
+    rows
+  }
+}
+
+impl LayoutEngine for GridLayout {
+  fn is_valid_layout(&self, wm: &WindowsManager, _target_num_master_windows: usize) -> Result<LayoutValidity> {
+    info!("Starting valid grid layout check...");
+    let len = wm.windows.len();
+    if len <= 1 {
+      info!("Layout is valid");
+      return Ok(LayoutValidity::Valid);
+    }
+
+    let num_columns = Self::get_num_columns(len);
+    let rows = Self::get_rows(wm);
+    let expected_num_rows = len.div_ceil(num_columns);
+    if rows.len() != expected_num_rows {
+      let reason =
+        format!("Number of rows does not equal expected number of rows ({}/{expected_num_rows})", rows.len());
+      error!("Layout invalid: {}", reason.red());
+      return Ok(LayoutValidity::Invalid(reason));
+    }
+
+    for (idx, row) in rows.iter().enumerate() {
+      let expected_len = if idx + 1 == expected_num_rows { len - num_columns * idx } else { num_columns };
+      if row.len() != expected_len {
+        let reason = format!("Row {idx} does not have the expected number of windows ({}/{expected_len})", row.len());
+        error!("Layout invalid: {}", reason.red());
+        return Ok(LayoutValidity::Invalid(reason));
+      }
+    }
+
+    info!("Layout is valid");
+    Ok(LayoutValidity::Valid)
+  }
+
+  fn update_windows(&self, wm: &mut WindowsManager, target_num_master_windows: usize) -> Result<()> {
+    if let LayoutValidity::Valid = self.is_valid_layout(wm, target_num_master_windows)? {
+      return Ok(());
+    }
+
+    let windows = self.get_ordered_windows(wm)?;
+    self.arrange_windows_in_order(wm, windows, target_num_master_windows)
+  }
+
+  fn arrange_windows_in_order(
+    &self, wm: &mut WindowsManager, windows: Vec<Window>, _target_num_master_windows: usize,
+  ) -> Result<()> {
+    let num_columns = Self::get_num_columns(windows.len()).max(1);
+    let rows = windows.chunks(num_columns).map(|row| row.to_vec()).collect::<Vec<_>>();
+    wm.arrange_windows(&rows, YabaiDirectionSelector::South, YabaiDirectionSelector::East)?;
+    trace!("Refreshing windows data after the re-tile");
+    wm.windows = wm.get_windows_data()?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,193 @@
+use std::fmt::{Display, Formatter};
+
+use color_eyre::owo_colors::OwoColorize;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  window_manager::{
+    layout_visibility::LayoutValidity,
+    layouts::{grid::GridLayout, monocle::MonocleLayout, tall::TallLayout, wide::WideLayout},
+    window_order::reconcile_window_order,
+    window_position::compare_position,
+    WindowsManager,
+  },
+  yabai::{
+    state::InsertionPoint,
+    window::{Window, WindowId},
+  },
+};
+
+mod grid;
+mod monocle;
+mod tall;
+mod wide;
+
+type Result<T> = color_eyre::Result<T>;
+
+/// The arrangement used to tile the windows of a space.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum Layout {
+  /// Master pane on one side of the display, stack pane on the other side
+  #[default]
+  Tall,
+  /// Master pane at the top of the display, stack pane below it
+  Wide,
+  /// Every window in a single yabai stack
+  Monocle,
+  /// Every window in an evenly sized grid
+  Grid,
+}
+
+impl Layout {
+  pub(crate) fn engine(self) -> &'static dyn LayoutEngine {
+    match self {
+      Layout::Tall => &TallLayout,
+      Layout::Wide => &WideLayout,
+      Layout::Monocle => &MonocleLayout,
+      Layout::Grid => &GridLayout,
+    }
+  }
+
+  /// The layout following this one when cycling through layouts.
+  pub(crate) fn next(self) -> Self {
+    match self {
+      Layout::Tall => Layout::Wide,
+      Layout::Wide => Layout::Monocle,
+      Layout::Monocle => Layout::Grid,
+      Layout::Grid => Layout::Tall,
+    }
+  }
+}
+
+impl Display for Layout {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let str = match self {
+      Layout::Tall => "Tall",
+      Layout::Wide => "Wide",
+      Layout::Monocle => "Monocle",
+      Layout::Grid => "Grid",
+    };
+    write!(f, "{:?}", str.yellow())
+  }
+}
+
+/// Implementation of a [Layout].
+pub(crate) trait LayoutEngine {
+  /// Checks whether the windows are arranged according to the layout.
+  fn is_valid_layout(&self, wm: &WindowsManager, target_num_master_windows: usize) -> Result<LayoutValidity>;
+
+  /// Arranges the windows according to the layout, if they are not already.
+  fn update_windows(&self, wm: &mut WindowsManager, target_num_master_windows: usize) -> Result<()>;
+
+  /// Arranges the windows according to the layout, when they were arranged by another layout before.
+  fn reset_windows(&self, wm: &mut WindowsManager, target_num_master_windows: usize) -> Result<()> {
+    self.update_windows(wm, target_num_master_windows)
+  }
+
+  /// Rebuilds the layout so that the windows fill its slots in the given order.
+  fn arrange_windows_in_order(
+    &self, wm: &mut WindowsManager, windows: Vec<Window>, target_num_master_windows: usize,
+  ) -> Result<()>;
+
+  /// The windows in the order of the slots of the layout.
+  fn get_ordered_windows(&self, wm: &WindowsManager) -> Result<Vec<Window>> {
+    let mut windows = wm.windows.clone();
+    windows.sort_by(compare_position);
+    Ok(windows)
+  }
+
+  /// Moves a newly created window to where the layout expects it, before the windows are updated.
+  fn place_new_window(&self, _wm: &WindowsManager, _window: &Window, _target_num_master_windows: usize) -> Result<()> {
+    Ok(())
+  }
+
+  /// The window order with a newly created window in the slot where the layout places it, if the layout has slots.
+  fn get_new_window_order(
+    &self, _wm: &WindowsManager, _window: &Window, _target_num_master_windows: usize,
+  ) -> Result<Option<Vec<WindowId>>> {
+    Ok(None)
+  }
+
+  /// Checks whether a newly created window landed in the slot where the layout expects it.
+  fn is_new_window_placed(
+    &self, _wm: &WindowsManager, _window: &Window, _target_num_master_windows: usize,
+  ) -> Result<bool> {
+    Ok(true)
+  }
+
+  /// Window next to which yabai should insert the next created window, and on which side.
+  fn get_insertion_point(&self, _wm: &WindowsManager) -> Result<Option<InsertionPoint>> { Ok(None) }
+
+  /// Share of the display taken by the master pane, if the layout has one.
+  fn get_master_ratio(&self, _wm: &WindowsManager) -> Result<Option<f64>> { Ok(None) }
+
+  /// Resizes the master pane so it takes the given share of the display, if the layout has one.
+  fn apply_master_ratio(&self, _wm: &WindowsManager, _ratio: f64) -> Result<()> { Ok(()) }
+}
+
+impl WindowsManager {
+  pub(crate) fn is_valid_layout(&self, target_num_master_windows: Option<usize>) -> Result<LayoutValidity> {
+    let target_num_master_windows = target_num_master_windows.unwrap_or(self.expected_current_num_master_windows);
+    self.layout.engine().is_valid_layout(self, target_num_master_windows)
+  }
+
+  pub fn update_windows(&mut self, target_num_master_windows: usize) -> Result<()> {
+    info!("update_windows(Layout = {}, Target master count = {target_num_master_windows})", self.layout);
+    let previous_slots = self.get_window_slots()?;
+    self.layout.engine().update_windows(self, target_num_master_windows)?;
+    self.apply_master_ratio()?;
+    self.update_retiled_window_order(&previous_slots)?;
+    self.update_insertion_point()
+  }
+
+  pub(crate) fn reset_windows(&mut self, target_num_master_windows: usize) -> Result<()> {
+    info!("reset_windows(Layout = {}, Target master count = {target_num_master_windows})", self.layout);
+    let previous_slots = self.get_window_slots()?;
+    self.layout.engine().reset_windows(self, target_num_master_windows)?;
+    self.apply_master_ratio()?;
+    self.update_retiled_window_order(&previous_slots)?;
+    self.update_insertion_point()
+  }
+
+  pub(crate) fn arrange_windows_in_order(
+    &mut self, windows: Vec<Window>, target_num_master_windows: usize,
+  ) -> Result<()> {
+    info!("arrange_windows_in_order(Layout = {}, Target master count = {target_num_master_windows})", self.layout);
+    let window_order = windows.iter().map(|window| window.id).collect::<Vec<_>>();
+    self.layout.engine().arrange_windows_in_order(self, windows, target_num_master_windows)?;
+    self.apply_master_ratio()?;
+    self.window_order = reconcile_window_order(&window_order, &self.window_order);
+    self.update_insertion_point()
+  }
+
+  pub(crate) fn get_ordered_windows(&self) -> Result<Vec<Window>> { self.layout.engine().get_ordered_windows(self) }
+}
+
+#[cfg(test)]
+mod tests {
+  use clap::ValueEnum;
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn cycling_goes_through_every_layout() {
+    let mut layout = Layout::default();
+    let mut visited = vec![];
+    for _ in Layout::value_variants() {
+      visited.push(layout);
+      layout = layout.next();
+    }
+
+    assert_eq!(layout, Layout::default());
+    assert_eq!(visited, Layout::value_variants());
+  }
+
+  #[test]
+  fn layout_is_serialized_in_camel_case() {
+    assert_eq!(serde_json::to_string(&Layout::Monocle).unwrap(), "\"monocle\"");
+    assert_eq!(serde_json::from_str::<Layout>("\"wide\"").unwrap(), Layout::Wide);
+  }
+}
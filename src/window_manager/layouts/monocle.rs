@@ -0,0 +1,75 @@
+use color_eyre::owo_colors::OwoColorize;
+use log::{error, info, trace};
+
+use crate::{
+  window_manager::{layout_visibility::LayoutValidity, layouts::LayoutEngine, WindowsManager},
+  yabai::{command::message::YabaiMessage, window::Window},
+};
+
+type Result<T> = color_eyre::Result<T>;
+
+/// Every window in a single yabai stack, taking the whole space.
+pub(super) struct MonocleLayout;
+
+impl LayoutEngine for MonocleLayout {
+  fn is_valid_layout(&self, wm: &WindowsManager, _target_num_master_windows: usize) -> Result<LayoutValidity> {
+    info!("Starting valid monocle layout check...");
+    let Some(first_window) = wm.windows.first() else {
+      info!("Layout is valid");
+      return Ok(LayoutValidity::Valid);
+    };
+    if wm.windows.len() == 1 {
+      info!("Layout is valid");
+      return Ok(LayoutValidity::Valid);
+    }
+
+    let unstacked_window = wm.windows.iter().find(|window| {
+      window.stack_index == 0 || window.frame.x != first_window.frame.x || window.frame.y != first_window.frame.y
+    });
+    if let Some(window) = unstacked_window {
+      let reason = format!("A window ({window}) is not part of the stack.");
+      error!("Layout invalid: {}", reason.red());
+      return Ok(LayoutValidity::Invalid(reason));
+    }
+
+    info!("Layout is valid");
+    Ok(LayoutValidity::Valid)
+  }
+
+  fn update_windows(&self, wm: &mut WindowsManager, target_num_master_windows: usize) -> Result<()> {
+    if let LayoutValidity::Valid = self.is_valid_layout(wm, target_num_master_windows)? {
+      return Ok(());
+    }
+
+    // Stacking on the focused window keeps it visible
+    let mut windows = self.get_ordered_windows(wm)?;
+    if let Some(position) = windows.iter().position(|window| window.has_focus) {
+      let focused_window = windows.remove(position);
+      windows.insert(0, focused_window);
+    }
+    self.arrange_windows_in_order(wm, windows, target_num_master_windows)
+  }
+
+  fn arrange_windows_in_order(
+    &self, wm: &mut WindowsManager, windows: Vec<Window>, _target_num_master_windows: usize,
+  ) -> Result<()> {
+    let Some(anchor) = windows.first() else {
+      return Ok(());
+    };
+    for window in windows.iter().skip(1) {
+      info!("Stacking {window} on {anchor}");
+      let message = YabaiMessage::window(anchor).stack(window)?;
+      wm.send_yabai_message(message)?;
+    }
+    trace!("Refreshing windows data after the re-tile");
+    wm.windows = wm.get_windows_data()?;
+
+    Ok(())
+  }
+
+  fn get_ordered_windows(&self, wm: &WindowsManager) -> Result<Vec<Window>> {
+    let mut windows = wm.windows.clone();
+    windows.sort_by_key(|window| window.stack_index);
+    Ok(windows)
+  }
+}
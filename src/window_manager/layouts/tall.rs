@@ -0,0 +1,210 @@
+use color_eyre::owo_colors::OwoColorize;
+use log::{debug, error, info, trace};
+
+use crate::{
+  window_manager::{
+    layout_visibility::LayoutValidity,
+    layouts::LayoutEngine,
+    window_order::{get_insertion_anchor, get_insertion_index},
+    window_position::{compare_position, compare_stack_position},
+    WindowsManager,
+  },
+  yabai::{
+    command::{
+      direction_selector::YabaiDirectionSelector, message::YabaiMessage, resize_selector::YabaiResizeSelector,
+    },
+    config::{get_config, MasterPosition},
+    state::InsertionPoint,
+    window::{Window, WindowId},
+  },
+};
+
+type Result<T> = color_eyre::Result<T>;
+
+/// Direction of the stack pane from the master pane.
+fn get_stack_direction() -> Result<YabaiDirectionSelector> {
+  let config = get_config()?;
+  let direction = match config.master_position {
+    MasterPosition::Left => YabaiDirectionSelector::East,
+    MasterPosition::Right => YabaiDirectionSelector::West,
+  };
+
+  Ok(direction)
+}
+
+/// The order of the other windows, and the slot of `window` in it according to its placement.
+fn get_new_window_slot(
+  wm: &WindowsManager, window: &Window, target_num_master_windows: usize,
+) -> Result<(Vec<WindowId>, usize)> {
+  let config = get_config()?;
+  let placement = config.get_new_window_placement(&window.app);
+  let order = wm.window_order.iter().filter(|id| **id != window.id).copied().collect::<Vec<_>>();
+  // The new window usually has the focus already, the focus history still has the window focused before it
+  let focused_window = wm.focus_history.iter().find(|id| **id != window.id && order.contains(id)).copied();
+  let index = get_insertion_index(placement, &order, focused_window, target_num_master_windows);
+  trace!("Slot of new window {window} with {placement:?}: {index}");
+
+  Ok((order, index))
+}
+
+/// Master pane on the side given by the `masterPosition` configuration, stack pane on the other side.
+pub(super) struct TallLayout;
+
+impl LayoutEngine for TallLayout {
+  fn is_valid_layout(&self, wm: &WindowsManager, target_num_master_windows: usize) -> Result<LayoutValidity> {
+    wm.is_valid_tall_layout(target_num_master_windows)
+  }
+
+  fn update_windows(&self, wm: &mut WindowsManager, target_num_master_windows: usize) -> Result<()> {
+    if wm.get_max_stack_rows()?.is_none() {
+      return wm.update_tall_windows(target_num_master_windows);
+    }
+
+    // Windows can't be moved one by one between stack columns, so an invalid layout is rebuilt in order
+    if let LayoutValidity::Valid = wm.is_valid_tall_layout(target_num_master_windows)? {
+      wm.expected_current_num_master_windows = target_num_master_windows;
+      return Ok(());
+    }
+    let mut windows = self.get_ordered_windows(wm)?;
+    let num_master_windows = wm.get_master_windows()?.len().min(target_num_master_windows);
+    for slot in num_master_windows..target_num_master_windows.min(windows.len()) {
+      if let Some(index) = wm.get_window_to_promote(&windows[slot..])? {
+        let window = windows.remove(slot + index);
+        windows.insert(slot, window);
+      }
+    }
+    self.arrange_windows_in_order(wm, windows, target_num_master_windows)
+  }
+
+  fn reset_windows(&self, wm: &mut WindowsManager, target_num_master_windows: usize) -> Result<()> {
+    // Trees built by other layouts can't be untangled by moving windows one by one, so they are rebuilt first
+    let mut windows = wm.windows.clone();
+    windows.sort_by(compare_position);
+    self.arrange_windows_in_order(wm, windows, target_num_master_windows)
+  }
+
+  fn arrange_windows_in_order(
+    &self, wm: &mut WindowsManager, mut windows: Vec<Window>, target_num_master_windows: usize,
+  ) -> Result<()> {
+    let stack_direction = get_stack_direction()?;
+    let stack_windows = windows.split_off(target_num_master_windows.min(windows.len()));
+    let mut groups = vec![windows];
+    groups.extend(wm.split_stack_into_columns(stack_windows)?);
+    wm.arrange_windows(&groups, stack_direction, YabaiDirectionSelector::South)?;
+    wm.windows = wm.get_windows_data()?;
+
+    if wm.get_max_stack_rows()?.is_none() {
+      return wm.update_tall_windows(target_num_master_windows);
+    }
+
+    wm.expected_current_num_master_windows = target_num_master_windows;
+    match wm.is_valid_tall_layout(target_num_master_windows)? {
+      LayoutValidity::Valid => Ok(()),
+      LayoutValidity::Invalid(reason) => {
+        error!("Stack columns are still invalid after arranging: {reason}");
+        Ok(())
+      },
+    }
+  }
+
+  fn get_ordered_windows(&self, wm: &WindowsManager) -> Result<Vec<Window>> {
+    let mut master_windows = wm.get_master_windows()?;
+    master_windows.sort_by(compare_position);
+    let mut stack_windows =
+      wm.windows.iter().filter(|window| !master_windows.iter().any(|w| w.id == window.id)).cloned().collect::<Vec<_>>();
+    // Stack windows are ordered column by column, starting with the column next to the master pane
+    let config = get_config()?;
+    stack_windows.sort_by(|w1, w2| compare_stack_position(&config.master_position, w1, w2));
+    master_windows.extend(stack_windows);
+
+    Ok(master_windows)
+  }
+
+  fn place_new_window(&self, wm: &WindowsManager, window: &Window, target_num_master_windows: usize) -> Result<()> {
+    let (order, index) = get_new_window_slot(wm, window, target_num_master_windows)?;
+    let anchor = get_insertion_anchor(index, &order, target_num_master_windows, get_stack_direction()?)
+      .and_then(|(id, direction)| wm.windows.iter().find(|w| w.id == id).map(|anchor| (anchor, direction)));
+
+    match anchor {
+      // Inserted in the master pane, the bottom master window is pushed to the stack by the update
+      Some((anchor, direction)) => wm.warp_window_next_to(window, anchor, &direction),
+      None => {
+        trace!("Moving new window {window} to stack");
+        wm.move_window_to_stack(window)
+      },
+    }
+  }
+
+  fn get_new_window_order(
+    &self, wm: &WindowsManager, window: &Window, target_num_master_windows: usize,
+  ) -> Result<Option<Vec<WindowId>>> {
+    let (mut order, index) = get_new_window_slot(wm, window, target_num_master_windows)?;
+    order.insert(index, window.id);
+
+    Ok(Some(order))
+  }
+
+  fn is_new_window_placed(
+    &self, wm: &WindowsManager, window: &Window, target_num_master_windows: usize,
+  ) -> Result<bool> {
+    let (_, index) = get_new_window_slot(wm, window, target_num_master_windows)?;
+    let position = wm.get_ordered_windows()?.iter().position(|w| w.id == window.id);
+    debug!("New window {window} is in slot {position:?}, expected in slot {index}");
+
+    Ok(position == Some(index))
+  }
+
+  fn get_insertion_point(&self, wm: &WindowsManager) -> Result<Option<InsertionPoint>> {
+    let config = get_config()?;
+    let placement = config.get_default_new_window_placement();
+    let focused_window = wm.get_focused_window().map(|window| window.id);
+    let num_master_windows = wm.expected_current_num_master_windows;
+    let index = get_insertion_index(placement, &wm.window_order, focused_window, num_master_windows);
+    let anchor = get_insertion_anchor(index, &wm.window_order, num_master_windows, get_stack_direction()?);
+
+    Ok(anchor.map(|(window, direction)| InsertionPoint { window, direction }))
+  }
+
+  fn get_master_ratio(&self, wm: &WindowsManager) -> Result<Option<f64>> {
+    let config = get_config()?;
+    let dividing_line_x_coordinate = wm.get_dividing_line_x_coordinate()?;
+    let frame = &wm.display.frame;
+    let ratio = match config.master_position {
+      MasterPosition::Left => (dividing_line_x_coordinate - frame.x) / frame.w,
+      MasterPosition::Right => (frame.x + frame.w - dividing_line_x_coordinate) / frame.w,
+    };
+    trace!("Current master ratio: {}", ratio.blue());
+
+    Ok(Some(ratio))
+  }
+
+  fn apply_master_ratio(&self, wm: &WindowsManager, ratio: f64) -> Result<()> {
+    let Some(master_window) = wm.get_top_master_window()? else {
+      debug!("Skipped applying master ratio because there is no master window");
+      return Ok(());
+    };
+
+    let config = get_config()?;
+    let dividing_line_x_coordinate = wm.get_dividing_line_x_coordinate()?;
+    let frame = &wm.display.frame;
+    let delta = match config.master_position {
+      MasterPosition::Left => frame.x + frame.w * ratio - dividing_line_x_coordinate,
+      MasterPosition::Right => frame.x + frame.w * (1f64 - ratio) - dividing_line_x_coordinate,
+    }
+    .round() as i32;
+    if delta == 0 {
+      debug!("Master ratio {} is already applied", ratio.blue());
+      return Ok(());
+    }
+
+    // The edge facing the stack is the one shared by the master and stack panes
+    let selector = match config.master_position {
+      MasterPosition::Left => YabaiResizeSelector::Right(delta, 0),
+      MasterPosition::Right => YabaiResizeSelector::Left(delta, 0),
+    };
+
+    info!("Applying master ratio {} to {master_window}", ratio.blue());
+    let message = YabaiMessage::window(&master_window).resize(selector)?;
+    wm.send_yabai_message(message)
+  }
+}
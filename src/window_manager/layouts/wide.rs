@@ -0,0 +1,113 @@
+use color_eyre::owo_colors::OwoColorize;
+use log::{debug, error, info, trace};
+
+use crate::{
+  window_manager::{layout_visibility::LayoutValidity, layouts::LayoutEngine, WindowsManager},
+  yabai::{
+    command::{
+      direction_selector::YabaiDirectionSelector, message::YabaiMessage, resize_selector::YabaiResizeSelector,
+    },
+    window::Window,
+  },
+};
+
+type Result<T> = color_eyre::Result<T>;
+
+/// Master pane at the top of the display, stack pane below it.
+pub(super) struct WideLayout;
+
+impl WideLayout {
+  /// The top of the stack pane, if there is one.
+  fn get_stack_y_coordinate(wm: &WindowsManager) -> Result<Option<f64>> {
+    let top_padding = YabaiMessage::config().top_padding()?;
+    let top = wm.display.frame.y + top_padding;
+    let stack_y_coordinate =
+      wm.windows.iter().map(|window| window.frame.y).filter(|&y| y != top).min_by(|y1, y2| y1.total_cmp(y2));
+
+    Ok(stack_y_coordinate)
+  }
+}
+
+impl LayoutEngine for WideLayout {
+  fn is_valid_layout(&self, wm: &WindowsManager, target_num_master_windows: usize) -> Result<LayoutValidity> {
+    info!("Starting valid wide layout check...");
+    if wm.windows.len() <= 1 {
+      info!("Layout is valid");
+      return Ok(LayoutValidity::Valid);
+    }
+
+    let top_padding = YabaiMessage::config().top_padding()?;
+    let top = wm.display.frame.y + top_padding;
+    let (master_windows, stack_windows): (Vec<_>, Vec<_>) = wm.windows.iter().partition(|window| window.frame.y == top);
+    let target_num_master_windows = target_num_master_windows.min(wm.windows.len());
+    if master_windows.len() != target_num_master_windows {
+      let reason = format!(
+        "Number of master windows does not equal expected number of master windows ({}/{target_num_master_windows})",
+        master_windows.len()
+      );
+      error!("Layout invalid: {}", reason.red());
+      return Ok(LayoutValidity::Invalid(reason));
+    }
+
+    if let Some(first_stack_window) = stack_windows.first() {
+      if let Some(window) = stack_windows.iter().find(|window| window.frame.y != first_stack_window.frame.y) {
+        let reason = format!("A stack window ({window}) is not on the stack row.");
+        error!("Layout invalid: {}", reason.red());
+        return Ok(LayoutValidity::Invalid(reason));
+      }
+    }
+
+    info!("Layout is valid");
+    Ok(LayoutValidity::Valid)
+  }
+
+  fn update_windows(&self, wm: &mut WindowsManager, target_num_master_windows: usize) -> Result<()> {
+    if let LayoutValidity::Valid = self.is_valid_layout(wm, target_num_master_windows)? {
+      return Ok(());
+    }
+
+    let windows = self.get_ordered_windows(wm)?;
+    self.arrange_windows_in_order(wm, windows, target_num_master_windows)
+  }
+
+  fn arrange_windows_in_order(
+    &self, wm: &mut WindowsManager, mut windows: Vec<Window>, target_num_master_windows: usize,
+  ) -> Result<()> {
+    let stack_windows = windows.split_off(target_num_master_windows.min(windows.len()));
+    wm.arrange_windows(&[windows, stack_windows], YabaiDirectionSelector::South, YabaiDirectionSelector::East)?;
+    trace!("Refreshing windows data after the re-tile");
+    wm.windows = wm.get_windows_data()?;
+
+    Ok(())
+  }
+
+  fn get_master_ratio(&self, wm: &WindowsManager) -> Result<Option<f64>> {
+    let frame = &wm.display.frame;
+    let ratio = Self::get_stack_y_coordinate(wm)?.map(|y| (y - frame.y) / frame.h);
+    trace!("Current master ratio: {ratio:?}");
+
+    Ok(ratio)
+  }
+
+  fn apply_master_ratio(&self, wm: &WindowsManager, ratio: f64) -> Result<()> {
+    let Some(stack_y_coordinate) = Self::get_stack_y_coordinate(wm)? else {
+      debug!("Skipped applying master ratio because there is no stack row");
+      return Ok(());
+    };
+    let Some(master_window) = self.get_ordered_windows(wm)?.into_iter().next() else {
+      debug!("Skipped applying master ratio because there is no master window");
+      return Ok(());
+    };
+
+    let frame = &wm.display.frame;
+    let delta = (frame.y + frame.h * ratio - stack_y_coordinate).round() as i32;
+    if delta == 0 {
+      debug!("Master ratio {} is already applied", ratio.blue());
+      return Ok(());
+    }
+
+    info!("Applying master ratio {} to {master_window}", ratio.blue());
+    let message = YabaiMessage::window(&master_window).resize(YabaiResizeSelector::Bottom(0, delta))?;
+    wm.send_yabai_message(message)
+  }
+}
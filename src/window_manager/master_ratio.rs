@@ -0,0 +1,43 @@
+use color_eyre::owo_colors::OwoColorize;
+use log::{debug, trace};
+
+use crate::window_manager::WindowsManager;
+
+type Result<T> = color_eyre::Result<T>;
+
+/// Smallest share of the display that the master pane can take.
+pub(crate) const MIN_MASTER_RATIO: f64 = 0.1;
+/// Largest share of the display that the master pane can take.
+pub(crate) const MAX_MASTER_RATIO: f64 = 0.9;
+
+impl WindowsManager {
+  fn has_stack(&self) -> bool {
+    self.windows.len() > 1 && self.expected_current_num_master_windows < self.windows.len()
+  }
+
+  /// Share of the display currently taken by the master pane, as laid out by yabai.
+  pub(crate) fn get_master_ratio(&self) -> Result<Option<f64>> {
+    if !self.has_stack() {
+      trace!("No stack, the master ratio is undefined");
+      return Ok(None);
+    }
+
+    self.layout.engine().get_master_ratio(self)
+  }
+
+  /// Resizes the master pane so it takes the stored master ratio of the display.
+  pub(crate) fn apply_master_ratio(&self) -> Result<()> {
+    let Some(ratio) = self.master_ratio else {
+      trace!("No master ratio stored for this space");
+      return Ok(());
+    };
+    if !self.has_stack() {
+      debug!("Skipped applying master ratio because there is no stack");
+      return Ok(());
+    }
+
+    let ratio = ratio.clamp(MIN_MASTER_RATIO, MAX_MASTER_RATIO);
+    debug!("Applying master ratio {} with layout {}", ratio.blue(), self.layout);
+    self.layout.engine().apply_master_ratio(self, ratio)
+  }
+}
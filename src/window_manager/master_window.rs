@@ -4,12 +4,32 @@ use crate::{
   window_manager::WindowsManager,
   yabai::{
     command::{message::YabaiMessage, toggle_selector::YabaiToggleSelector},
-    config::{get_config, MasterPosition},
-    window::{SplitType, Window},
+    config::{get_config, MasterPosition, MasterPromotion},
+    window::{SplitType, Window, WindowId},
   },
 };
 
 type Result<T> = color_eyre::Result<T>;
+
+/// Index of the window to promote to master among `stack`, ordered from top to bottom, for the given promotion.
+pub(crate) fn get_stack_window_to_promote(
+  promotion: MasterPromotion, stack: &[WindowId], focus_history: &[WindowId],
+) -> Option<usize> {
+  if stack.is_empty() {
+    return None;
+  }
+
+  let index = match promotion {
+    MasterPromotion::BottomOfStack => stack.len() - 1,
+    MasterPromotion::MostRecentlyFocused => {
+      focus_history.iter().find_map(|id| stack.iter().position(|stack_id| stack_id == id)).unwrap_or_default()
+    },
+    // The master count only shrinks when a master window disappears, raising it still promotes windows
+    MasterPromotion::TopOfStack | MasterPromotion::None => 0,
+  };
+
+  Some(index)
+}
 impl WindowsManager {
   pub(crate) fn get_master_windows(&self) -> Result<Vec<Window>> {
     debug!("Looking for master windows");
@@ -29,15 +49,6 @@ impl WindowsManager {
     Ok(self.get_top_window(self.get_master_windows()?))
   }
 
-  pub(crate) fn get_bottom_master_window(&self) -> Result<Option<Window>> {
-    Ok(self.get_bottom_window(self.get_master_windows()?))
-  }
-
-  pub(crate) fn get_master_window(&self) -> Result<Option<Window>> {
-    let master_windows = self.get_master_windows()?;
-    Ok(master_windows.first().cloned())
-  }
-
   pub(crate) fn is_master_window(&self, window: &Window) -> Result<bool> {
     let config = get_config()?;
     match config.master_position {
@@ -49,6 +60,17 @@ impl WindowsManager {
     }
   }
 
+  /// Index of the window to promote to master among `stack_windows`, sorted from top to bottom, according to the
+  /// `masterPromotion` configuration.
+  pub(crate) fn get_window_to_promote(&self, stack_windows: &[Window]) -> Result<Option<usize>> {
+    let config = get_config()?;
+    let stack = stack_windows.iter().map(|window| window.id).collect::<Vec<_>>();
+    let index = get_stack_window_to_promote(config.master_promotion, &stack, &self.focus_history);
+    debug!("Promoting stack window {index:?} with {:?}", config.master_promotion);
+
+    Ok(index)
+  }
+
   pub(crate) fn move_window_to_master(&self, window: &Window) -> Result<()> {
     debug!("Moving window {window} to master.");
     if self.expected_current_num_master_windows < self.windows.len() {
@@ -1,20 +1,35 @@
+use std::cell::Cell;
+
 use color_eyre::{eyre::eyre, owo_colors::OwoColorize};
 use log::{debug, trace};
 
 use crate::{
   print_bool,
-  yabai::{command::message::YabaiMessage, display::Display, spaces::Space, state::State, window::Window},
+  window_manager::layouts::Layout,
+  yabai::{
+    command::message::YabaiMessage,
+    display::Display,
+    spaces::Space,
+    state::{InsertionPoint, State},
+    window::{Window, WindowId},
+  },
 };
 
+mod arrange;
 mod columnize;
+mod insertion_point;
 mod layout;
 pub mod layout_visibility;
+pub mod layouts;
+pub mod master_ratio;
 mod master_window;
 mod stack;
+mod stack_columns;
 mod stack_window;
 pub mod update_windows;
 pub mod widest_window;
-mod window_position;
+pub(crate) mod window_order;
+pub(crate) mod window_position;
 pub mod windows;
 pub mod yabai;
 
@@ -22,18 +37,39 @@ pub struct WindowsManager {
   pub(crate) display: Display,
   pub(crate) space: Space,
   pub(crate) expected_current_num_master_windows: usize,
+  pub(crate) master_ratio: Option<f64>,
+  pub(crate) layout: Layout,
+  pub(crate) window_order: Vec<WindowId>,
+  pub(crate) focus_history: Vec<WindowId>,
+  pub(crate) insertion_point: Option<InsertionPoint>,
+  /// Whether windows were re-inserted in the tree since the insertion point was set
+  pub(crate) has_inserted_windows: Cell<bool>,
   pub(crate) windows: Vec<Window>,
 }
 
 type Result<T> = color_eyre::Result<T>;
 
 impl WindowsManager {
-  pub fn new(display: Display, space: Space, expected_current_num_master_windows: usize) -> Self {
+  pub fn new(
+    display: Display, space: Space, expected_current_num_master_windows: usize, master_ratio: Option<f64>,
+    layout: Layout, window_order: Vec<WindowId>,
+  ) -> Self {
     trace!(
-      "Creating new WindowsManager with {display:?} {space:?} {expected_current_num_master_windows}",
+      "Creating new WindowsManager with {display:?} {space:?} {expected_current_num_master_windows} {master_ratio:?} {layout} {window_order:?}",
       expected_current_num_master_windows = expected_current_num_master_windows.blue()
     );
-    Self { display, expected_current_num_master_windows, space, windows: vec![] }
+    Self {
+      display,
+      expected_current_num_master_windows,
+      master_ratio,
+      layout,
+      window_order,
+      focus_history: vec![],
+      insertion_point: None,
+      has_inserted_windows: Cell::new(false),
+      space,
+      windows: vec![],
+    }
   }
 
   pub fn windows(&self) -> &Vec<Window> { &self.windows }
@@ -41,6 +77,7 @@ impl WindowsManager {
   pub fn initialize(&mut self) -> Result<()> {
     debug!("Initializing window manager");
     self.windows = self.get_windows_data()?;
+    self.reconcile_window_order()?;
 
     Ok(())
   }
@@ -88,8 +125,6 @@ impl WindowsManager {
     }
     debug!("State validated: {state:?}");
 
-    state.write_state()?;
-
     Ok(())
   }
 
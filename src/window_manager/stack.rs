@@ -5,12 +5,31 @@ use crate::{
   window_manager::WindowsManager,
   yabai::{
     command::{message::YabaiMessage, toggle_selector::YabaiToggleSelector},
-    window::SplitType,
+    config::{get_config, StackMode},
+    window::{SplitType, Window},
   },
 };
 
 type Result<T> = color_eyre::Result<T>;
 
+/// The stack windows to stack on `top_stack_window`, leaving out the windows already in its yabai stack.
+fn get_windows_to_deck(top_stack_window: &Window, stack_windows: &[Window]) -> Vec<Window> {
+  stack_windows
+    .iter()
+    .filter(|window| window.id != top_stack_window.id)
+    .filter(|window| {
+      let is_stacked = window.stack_index > 0
+        && window.frame.x == top_stack_window.frame.x
+        && window.frame.y == top_stack_window.frame.y;
+      if is_stacked {
+        debug!("Window {window} is already stacked");
+      }
+      !is_stacked
+    })
+    .cloned()
+    .collect()
+}
+
 impl WindowsManager {
   pub(crate) fn does_stack_exists(&self) -> bool {
     debug!("Checking if stack exists");
@@ -39,4 +58,50 @@ impl WindowsManager {
 
     Ok(())
   }
+
+  pub(crate) fn is_deck_mode(&self) -> color_eyre::Result<bool> {
+    let config = get_config()?;
+    Ok(config.stack_mode == StackMode::Deck)
+  }
+
+  /// Stack every stack window in the same yabai stack, on top of the top stack window.
+  pub(crate) fn deck_stack_windows(&self) -> Result<()> {
+    let stack_windows = self.get_stack_windows();
+    let Some(top_stack_window) = self.get_top_window(stack_windows.clone()) else {
+      debug!("No stack windows to stack");
+      return Ok(());
+    };
+
+    info!("Stacking stack windows on {top_stack_window}");
+    for window in get_windows_to_deck(&top_stack_window, &stack_windows) {
+      let message = YabaiMessage::window(&top_stack_window).stack(&window)?;
+      self.send_yabai_message(message)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn deck_stacks_windows_not_yet_on_the_top_stack_window() {
+    let top = Window::new_for_test(1, (960.0, 0.0, 960.0, 1080.0), 1);
+    let stacked = Window::new_for_test(2, (960.0, 0.0, 960.0, 1080.0), 2);
+    let tiled = Window::new_for_test(3, (960.0, 540.0, 960.0, 540.0), 0);
+    let other_stack = Window::new_for_test(4, (960.0, 540.0, 960.0, 540.0), 1);
+
+    let windows = get_windows_to_deck(&top, &[top.clone(), stacked, tiled, other_stack]);
+    assert_eq!(windows.iter().map(|window| window.id).collect::<Vec<_>>(), vec![3, 4]);
+  }
+
+  #[test]
+  fn deck_of_single_stack_window_is_empty() {
+    let top = Window::new_for_test(1, (960.0, 0.0, 960.0, 1080.0), 0);
+    assert!(get_windows_to_deck(&top, std::slice::from_ref(&top)).is_empty());
+  }
 }
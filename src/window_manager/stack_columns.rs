@@ -0,0 +1,158 @@
+use color_eyre::eyre::eyre;
+use log::{debug, error, trace};
+
+use crate::{
+  window_manager::WindowsManager,
+  yabai::{
+    config::{get_config, MasterPosition},
+    window::Window,
+  },
+};
+
+type Result<T> = color_eyre::Result<T>;
+
+/// Number of stack columns holding `num_stack_windows` with at most `max_stack_rows` windows each.
+fn get_num_columns(num_stack_windows: usize, max_stack_rows: Option<usize>) -> usize {
+  match max_stack_rows {
+    _ if num_stack_windows == 0 => 0,
+    Some(max_stack_rows) => num_stack_windows.div_ceil(max_stack_rows),
+    None => 1,
+  }
+}
+
+/// Split the stack windows, in order, into columns of at most `max_stack_rows` windows.
+fn split_into_columns(stack_windows: Vec<Window>, max_stack_rows: Option<usize>) -> Vec<Vec<Window>> {
+  match max_stack_rows {
+    Some(max_stack_rows) => stack_windows.chunks(max_stack_rows).map(<[Window]>::to_vec).collect(),
+    None => vec![stack_windows],
+  }
+}
+
+impl WindowsManager {
+  /// Maximum number of windows in a stack column, if the stack is allowed to overflow into more columns.
+  ///
+  /// Deck mode keeps every stack window in a single yabai stack, so it never overflows.
+  pub(crate) fn get_max_stack_rows(&self) -> Result<Option<usize>> {
+    if self.is_deck_mode()? {
+      return Ok(None);
+    }
+
+    let config = get_config()?;
+    Ok(config.max_stack_rows.filter(|&max_stack_rows| max_stack_rows > 0))
+  }
+
+  /// Number of stack columns needed to hold the stack windows for the given number of master windows.
+  pub(crate) fn get_num_stack_columns(&self, target_num_master_windows: usize) -> Result<usize> {
+    let num_stack_windows = self.windows.len().saturating_sub(target_num_master_windows);
+    let num_stack_columns = get_num_columns(num_stack_windows, self.get_max_stack_rows()?);
+    trace!("Number of stack columns for {num_stack_windows} stack windows: {num_stack_columns}");
+
+    Ok(num_stack_columns)
+  }
+
+  /// Split the stack windows, in order, into the columns they should be arranged in.
+  ///
+  /// The first column is the one next to the master pane.
+  pub(crate) fn split_stack_into_columns(&self, stack_windows: Vec<Window>) -> Result<Vec<Vec<Window>>> {
+    Ok(split_into_columns(stack_windows, self.get_max_stack_rows()?))
+  }
+
+  /// Current stack columns, starting with the one next to the master pane.
+  pub(crate) fn get_stack_columns(&self) -> Result<Vec<Vec<Window>>> {
+    let config = get_config()?;
+    let mut stack_windows = self.get_stack_windows();
+    stack_windows.sort_by(|w1, w2| {
+      let by_column = match config.master_position {
+        MasterPosition::Left => w1.frame.x.total_cmp(&w2.frame.x),
+        MasterPosition::Right => w2.frame.x.total_cmp(&w1.frame.x),
+      };
+      by_column.then(w1.frame.y.total_cmp(&w2.frame.y))
+    });
+
+    let mut columns: Vec<Vec<Window>> = vec![];
+    for window in stack_windows {
+      match columns.last_mut() {
+        Some(column) if column.first().is_some_and(|first| first.frame.x == window.frame.x) => column.push(window),
+        _ => columns.push(vec![window]),
+      }
+    }
+    debug!("Found {len} stack columns", len = columns.len());
+
+    Ok(columns)
+  }
+
+  /// Dividing line between the master pane and the stack columns, when the stack can span several columns.
+  ///
+  /// The master pane is always the outermost column on its side, so the line is the closest column to it.
+  pub(crate) fn get_column_dividing_line_x_coordinate(&self) -> Result<f64> {
+    let config = get_config()?;
+    let result = match config.master_position {
+      MasterPosition::Left => {
+        self
+          .windows
+          .iter()
+          .filter(|&window| {
+            !self
+              .is_windows_touching_left_edge(window)
+              .inspect_err(|err| error!("Error while checking if windows touch: {err}"))
+              .is_ok_and(|is_touching| is_touching)
+          })
+          .map(|window| window.frame.x)
+          .min_by(f64::total_cmp)
+          .unwrap_or(self.display.frame.x)
+      },
+      MasterPosition::Right => {
+        self
+          .windows
+          .iter()
+          .map(|window| window.frame.x)
+          .max_by(f64::total_cmp)
+          .ok_or(eyre!("get_column_dividing_line_x_coordinate: no windows"))?
+      },
+    };
+    trace!("get_column_dividing_line_x_coordinate: {result}");
+
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  fn get_stack_windows(num_windows: usize) -> Vec<Window> {
+    (1..=num_windows).map(|id| Window::new_for_test(id, (960.0, 0.0, 960.0, 1080.0), 0)).collect()
+  }
+
+  fn get_ids(columns: &[Vec<Window>]) -> Vec<Vec<usize>> {
+    columns.iter().map(|column| column.iter().map(|window| window.id).collect()).collect()
+  }
+
+  #[test]
+  fn num_columns_rounds_up() {
+    assert_eq!(get_num_columns(5, Some(2)), 3);
+    assert_eq!(get_num_columns(4, Some(2)), 2);
+    assert_eq!(get_num_columns(1, Some(3)), 1);
+  }
+
+  #[test]
+  fn num_columns_without_stack_windows_or_limit() {
+    assert_eq!(get_num_columns(0, Some(2)), 0);
+    assert_eq!(get_num_columns(0, None), 0);
+    assert_eq!(get_num_columns(7, None), 1);
+  }
+
+  #[test]
+  fn columns_are_filled_in_order() {
+    let columns = split_into_columns(get_stack_windows(5), Some(2));
+    assert_eq!(get_ids(&columns), vec![vec![1, 2], vec![3, 4], vec![5]]);
+  }
+
+  #[test]
+  fn single_column_without_limit() {
+    let columns = split_into_columns(get_stack_windows(3), None);
+    assert_eq!(get_ids(&columns), vec![vec![1, 2, 3]]);
+  }
+}
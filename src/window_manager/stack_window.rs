@@ -28,7 +28,11 @@ impl WindowsManager {
   pub(crate) fn is_stack_window(&self, window: &Window) -> Result<bool> {
     trace!("Checking that {window} is not a stacked window");
     let config = get_config()?;
+    let has_stack_columns = self.get_max_stack_rows()?.is_some();
     let result = match config.master_position {
+      // With several stack columns, everything that isn't in the master pane is part of the stack
+      MasterPosition::Left if has_stack_columns => !self.is_windows_touching_left_edge(window)?,
+      MasterPosition::Right if has_stack_columns => window.frame.x < self.get_dividing_line_x_coordinate()?,
       MasterPosition::Left => {
         let dividing_line_x_coordinate = self.get_dividing_line_x_coordinate()?;
         trace!("Dividing line x coordinate: {dividing_line_x_coordinate} {x}", x = window.frame.x);
@@ -1,17 +1,20 @@
-use color_eyre::{eyre::bail, owo_colors::OwoColorize};
+use color_eyre::{
+  eyre::{bail, eyre},
+  owo_colors::OwoColorize,
+};
 use log::{debug, info, trace, warn};
 
 use crate::{
-  window_manager::{layout_visibility::LayoutValidity, WindowsManager},
+  window_manager::{layout_visibility::LayoutValidity, window_position::compare_position, WindowsManager},
   yabai::{
     command::{message::YabaiMessage, toggle_selector::YabaiToggleSelector},
-    window::{SplitType, Window},
+    window::SplitType,
   },
 };
 
 impl WindowsManager {
-  pub fn update_windows(&mut self, target_num_master_windows: usize) -> color_eyre::Result<()> {
-    info!("update_windows(Target master count = {target_num_master_windows})");
+  pub(crate) fn update_tall_windows(&mut self, target_num_master_windows: usize) -> color_eyre::Result<()> {
+    info!("update_tall_windows(Target master count = {target_num_master_windows})");
     if target_num_master_windows == 0 {
       bail!("Target number of master windows cannot be 0.");
     }
@@ -30,20 +33,10 @@ impl WindowsManager {
     self.columnize_stack_windows()?;
     debug!("Columnize stack windows");
 
-    let layout_validity = self.is_valid_layout(Some(target_num_master_windows))?;
-    let sort = |w1: &Window, w2: &Window| {
-      if w1.frame.y == w2.frame.y {
-        w1.frame.x.total_cmp(&w2.frame.x)
-      } else {
-        w1.frame.y.total_cmp(&w2.frame.y)
-      }
-    };
+    let layout_validity = self.is_valid_tall_layout(target_num_master_windows)?;
     debug!("Layout validity: {layout_validity:?}");
     match layout_validity {
-      LayoutValidity::Valid => {
-        info!("Layout is valid");
-        Ok(())
-      },
+      LayoutValidity::Valid => info!("Layout is valid"),
       LayoutValidity::Invalid(reason) => {
         info!("Invalid layout detected: {reason}. Updating windows...");
         trace!("Windows: {windows:?}", windows = self.windows);
@@ -74,7 +67,7 @@ impl WindowsManager {
           if cur_num_master_windows > target_num_master_windows {
             info!("Too many master windows ({cur_num_master_windows}/{target_num_master_windows}).");
           }
-          master_windows.sort_by(sort);
+          master_windows.sort_by(compare_position);
 
           let location = "stack".blue();
           while cur_num_master_windows > target_num_master_windows {
@@ -118,7 +111,7 @@ impl WindowsManager {
             #[cfg(debug_assertions)]
             let max = 1;
             if dead_lock > max {
-              bail!("Dead lock detected while moving middle windows.")
+              bail!("Dead lock detected while moving middle windows.");
             }
             debug!("-------------------------------------------------------------");
             middle_windows = self.get_middle_windows();
@@ -127,30 +120,39 @@ impl WindowsManager {
           }
 
           let mut stack_windows = self.get_stack_windows();
-          stack_windows.sort_by(sort);
+          stack_windows.sort_by(compare_position);
 
           while cur_num_master_windows < target_num_master_windows {
             info!("Not enough master windows ({}/{})", cur_num_master_windows.blue(), target_num_master_windows.blue());
-            let stack_window = stack_windows.pop().unwrap();
+            let index = self.get_window_to_promote(&stack_windows)?.ok_or(eyre!("No stack window to promote"))?;
+            let stack_window = stack_windows.remove(index);
             info!("Moving stack window {stack_window} to master.",);
             self.move_window_to_master(&stack_window)?;
             cur_num_master_windows += 1;
           }
 
-          let result = self.is_valid_layout(Some(target_num_master_windows))?;
+          if self.is_deck_mode()? {
+            trace!("Refreshing windows data before stacking the stack windows");
+            self.windows = self.get_windows_data()?;
+            self.deck_stack_windows()?;
+          }
+
+          trace!("Refreshing windows data after the re-tile");
+          self.windows = self.get_windows_data()?;
+          let result = self.is_valid_tall_layout(target_num_master_windows)?;
           match result {
             LayoutValidity::Valid => info!("update_windows() was successful."),
             LayoutValidity::Invalid(reason) => {
-              bail!("update_windows() ended with and invalid layout; reason {reason}")
+              bail!("update_windows() ended with and invalid layout; reason {reason}");
             },
           }
           self.expected_current_num_master_windows = target_num_master_windows;
         } else {
           debug!("Only one window is open, no need to update windows.");
         }
-
-        Ok(())
       },
     }
+
+    Ok(())
   }
 }
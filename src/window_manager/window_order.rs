@@ -0,0 +1,309 @@
+use log::{debug, trace};
+
+use crate::{
+  window_manager::{master_window::get_stack_window_to_promote, WindowsManager},
+  yabai::{
+    command::{direction_selector::YabaiDirectionSelector, message::YabaiMessage},
+    config::{MasterPromotion, NewWindowPlacement},
+    window::{Window, WindowId},
+  },
+};
+
+type Result<T> = color_eyre::Result<T>;
+
+/// Reconcile a saved window order with the windows currently in the space.
+///
+/// Windows that no longer exist are dropped, and new windows are appended in the order they appear in
+/// `current_windows`.
+pub(crate) fn reconcile_window_order(order: &[WindowId], current_windows: &[WindowId]) -> Vec<WindowId> {
+  let mut result = order.iter().filter(|id| current_windows.contains(id)).copied().collect::<Vec<_>>();
+  for id in current_windows {
+    if !result.contains(id) {
+      result.push(*id);
+    }
+  }
+
+  result
+}
+
+/// The window order after a re-tile that left the windows in `current_slots`, the order of the slots of the layout.
+///
+/// When the re-tile moved windows to other slots, by promoting, demoting or placing them, the order follows the layout.
+/// Otherwise the saved order is kept, as the layout can't tell apart some reorders.
+pub(crate) fn get_retiled_window_order(
+  order: &[WindowId], previous_slots: &[WindowId], current_slots: &[WindowId],
+) -> Vec<WindowId> {
+  if previous_slots == current_slots {
+    reconcile_window_order(order, current_slots)
+  } else {
+    current_slots.to_vec()
+  }
+}
+
+/// The window order and the master count once the master window in slot `position` of `order` is gone.
+///
+/// The stack window picked by `promotion` takes the slot of the master window, or the master count shrinks with
+/// `MasterPromotion::None`.
+pub(crate) fn get_order_without_master(
+  order: &[WindowId], position: usize, master_count: usize, promotion: MasterPromotion, focus_history: &[WindowId],
+) -> (Vec<WindowId>, usize) {
+  let mut order = order.to_vec();
+  if position < order.len() {
+    order.remove(position);
+  }
+  if promotion == MasterPromotion::None && master_count > 1 {
+    return (order, master_count - 1);
+  }
+
+  let stack_start = (master_count - 1).min(order.len());
+  if let Some(index) = get_stack_window_to_promote(promotion, &order[stack_start..], focus_history) {
+    let id = order.remove(stack_start + index);
+    order.insert(position.min(stack_start), id);
+  }
+
+  (order, master_count)
+}
+
+/// Slot of a new window in `order`, the order of the other windows, for the given placement.
+pub(crate) fn get_insertion_index(
+  placement: NewWindowPlacement, order: &[WindowId], focused_window: Option<WindowId>, num_master_windows: usize,
+) -> usize {
+  let focused_position = focused_window.and_then(|focused_window| order.iter().position(|id| *id == focused_window));
+  match placement {
+    NewWindowPlacement::Master => 0,
+    NewWindowPlacement::StackTop => num_master_windows.min(order.len()),
+    NewWindowPlacement::StackBottom => order.len(),
+    NewWindowPlacement::AfterFocused => focused_position.map(|position| position + 1).unwrap_or(order.len()),
+    NewWindowPlacement::BeforeFocused => focused_position.unwrap_or(order.len()),
+  }
+}
+
+/// Window next to which a window inserted in slot `index` of `order` goes, and on which side.
+///
+/// Without a stack, the window starts one next to the master pane, in `stack_direction`.
+pub(crate) fn get_insertion_anchor(
+  index: usize, order: &[WindowId], num_master_windows: usize, stack_direction: YabaiDirectionSelector,
+) -> Option<(WindowId, YabaiDirectionSelector)> {
+  let num_master_windows = num_master_windows.min(order.len());
+  if index < order.len() && index <= num_master_windows {
+    order.get(index).map(|id| (*id, YabaiDirectionSelector::North))
+  } else if index > num_master_windows {
+    order.get(index - 1).map(|id| (*id, YabaiDirectionSelector::South))
+  } else {
+    order.last().map(|id| (*id, stack_direction))
+  }
+}
+
+/// `order` with every window moved `offset` slots, the first `master_count` windows being master windows.
+///
+/// Moving forward, stack windows go up the stack, the first stack window becomes the first master window, master
+/// windows go down the master pane and the last master window goes to the bottom of the stack.
+pub(crate) fn get_rotated_window_order(order: &[WindowId], master_count: usize, offset: isize) -> Vec<WindowId> {
+  if order.is_empty() {
+    return vec![];
+  }
+
+  let (master_windows, stack_windows) = order.split_at(master_count.min(order.len()));
+  // The slots in the order windows go through when moving forward
+  let mut cycle = stack_windows.iter().rev().chain(master_windows).copied().collect::<Vec<_>>();
+  cycle.rotate_right(offset.rem_euclid(order.len() as isize) as usize);
+  let (stack_windows, master_windows) = cycle.split_at(stack_windows.len());
+
+  master_windows.iter().chain(stack_windows.iter().rev()).copied().collect()
+}
+
+/// Swaps that turn the `current` order of the slots into the `target` order.
+///
+/// Slots are filled from the first one, by swapping the window expected in a slot with the window occupying it.
+pub(crate) fn get_swaps_to_order(current: &[WindowId], target: &[WindowId]) -> Vec<(WindowId, WindowId)> {
+  let mut current = current.to_vec();
+  let mut swaps = vec![];
+  for (slot, id) in target.iter().enumerate() {
+    let Some(position) = current.iter().position(|current_id| current_id == id) else {
+      continue;
+    };
+    if position != slot && slot < current.len() {
+      swaps.push((current[slot], *id));
+      current.swap(slot, position);
+    }
+  }
+
+  swaps
+}
+
+impl WindowsManager {
+  /// The windows in the order of the slots of the layout.
+  pub(crate) fn get_window_slots(&self) -> Result<Vec<WindowId>> {
+    Ok(self.get_ordered_windows()?.iter().map(|window| window.id).collect())
+  }
+
+  /// Bring the window order up to date with the windows reported by yabai.
+  ///
+  /// The order itself only changes when windows are explicitly reordered, it isn't taken from their positions.
+  pub(crate) fn reconcile_window_order(&mut self) -> Result<()> {
+    self.window_order = reconcile_window_order(&self.window_order, &self.get_window_slots()?);
+    trace!("Reconciled window order: {:?}", self.window_order);
+
+    Ok(())
+  }
+
+  /// Bring the window order up to date after a re-tile of the windows that were in `previous_slots`.
+  pub(crate) fn update_retiled_window_order(&mut self, previous_slots: &[WindowId]) -> Result<()> {
+    self.window_order = get_retiled_window_order(&self.window_order, previous_slots, &self.get_window_slots()?);
+    trace!("Window order after the re-tile: {:?}", self.window_order);
+
+    Ok(())
+  }
+
+  /// Move the windows into the slots given by `window_order`, by swapping windows two by two.
+  pub(crate) fn apply_window_order(&mut self, window_order: Vec<WindowId>) -> Result<()> {
+    let swaps = get_swaps_to_order(&self.window_order, &window_order);
+    debug!("Applying window order {window_order:?} with {len} swaps", len = swaps.len());
+    for (occupant, id) in swaps {
+      let message = YabaiMessage::window(id).swap(occupant)?;
+      self.send_yabai_message(message)?;
+    }
+    self.window_order = reconcile_window_order(&window_order, &self.window_order);
+    self.windows = self.get_windows_data()?;
+
+    self.update_insertion_point()
+  }
+
+  /// The windows of the space, master windows first, then stack windows.
+  pub(crate) fn get_windows_in_order(&self) -> Vec<Window> {
+    self.window_order.iter().filter_map(|id| self.windows.iter().find(|window| window.id == *id)).cloned().collect()
+  }
+
+  /// The window `offset` slots away from `window` in the window order, wrapping around at both ends.
+  pub(crate) fn get_window_at_offset(&self, window: &Window, offset: isize) -> Option<Window> {
+    let windows = self.get_windows_in_order();
+    let position = windows.iter().position(|w| w.id == window.id)?;
+    let index = (position as isize + offset).rem_euclid(windows.len() as isize) as usize;
+
+    windows.get(index).cloned()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn insertion_index_for_each_placement() {
+    let order = [1, 2, 3, 4];
+    assert_eq!(get_insertion_index(NewWindowPlacement::Master, &order, Some(3), 2), 0);
+    assert_eq!(get_insertion_index(NewWindowPlacement::StackTop, &order, Some(3), 2), 2);
+    assert_eq!(get_insertion_index(NewWindowPlacement::StackBottom, &order, Some(3), 2), 4);
+    assert_eq!(get_insertion_index(NewWindowPlacement::AfterFocused, &order, Some(3), 2), 3);
+    assert_eq!(get_insertion_index(NewWindowPlacement::BeforeFocused, &order, Some(3), 2), 2);
+  }
+
+  #[test]
+  fn insertion_index_without_focused_window_goes_to_the_end() {
+    assert_eq!(get_insertion_index(NewWindowPlacement::AfterFocused, &[1, 2], None, 1), 2);
+    assert_eq!(get_insertion_index(NewWindowPlacement::BeforeFocused, &[1, 2], Some(7), 1), 2);
+  }
+
+  #[test]
+  fn insertion_index_on_top_of_missing_stack() {
+    assert_eq!(get_insertion_index(NewWindowPlacement::StackTop, &[1], None, 2), 1);
+  }
+
+  #[test]
+  fn insertion_anchor_in_each_pane() {
+    let order = [1, 2, 3, 4];
+    let east = YabaiDirectionSelector::East;
+    assert_eq!(get_insertion_anchor(0, &order, 2, east.clone()), Some((1, YabaiDirectionSelector::North)));
+    assert_eq!(get_insertion_anchor(2, &order, 2, east.clone()), Some((3, YabaiDirectionSelector::North)));
+    assert_eq!(get_insertion_anchor(4, &order, 2, east), Some((4, YabaiDirectionSelector::South)));
+  }
+
+  #[test]
+  fn insertion_anchor_without_stack() {
+    let east = YabaiDirectionSelector::East;
+    assert_eq!(get_insertion_anchor(2, &[1, 2], 2, east.clone()), Some((2, YabaiDirectionSelector::East)));
+    assert_eq!(get_insertion_anchor(0, &[], 1, east), None);
+  }
+
+  #[test]
+  fn rotating_with_one_master_window() {
+    assert_eq!(get_rotated_window_order(&[1, 2, 3, 4], 1, 1), vec![2, 3, 4, 1]);
+    assert_eq!(get_rotated_window_order(&[1, 2, 3, 4], 1, -1), vec![4, 1, 2, 3]);
+  }
+
+  #[test]
+  fn rotating_sends_the_last_master_window_to_the_bottom_of_the_stack() {
+    assert_eq!(get_rotated_window_order(&[1, 2, 3, 4, 5], 2, 1), vec![3, 1, 4, 5, 2]);
+  }
+
+  #[test]
+  fn rotating_back_sends_the_first_master_window_to_the_top_of_the_stack() {
+    assert_eq!(get_rotated_window_order(&[1, 2, 3, 4, 5], 2, -1), vec![2, 5, 1, 3, 4]);
+    assert_eq!(get_rotated_window_order(&get_rotated_window_order(&[1, 2, 3, 4, 5], 2, 1), 2, -1), vec![1, 2, 3, 4, 5]);
+  }
+
+  #[test]
+  fn rotating_without_stack_moves_the_master_windows() {
+    assert_eq!(get_rotated_window_order(&[1, 2, 3], 3, 1), vec![3, 1, 2]);
+  }
+
+  #[test]
+  fn swaps_rotate_windows() {
+    assert_eq!(get_swaps_to_order(&[1, 2, 3, 4], &[2, 3, 4, 1]), vec![(1, 2), (1, 3), (1, 4)]);
+  }
+
+  #[test]
+  fn no_swaps_for_same_order() {
+    assert_eq!(get_swaps_to_order(&[1, 2, 3], &[1, 2, 3]), vec![]);
+  }
+
+  #[test]
+  fn reconcile_keeps_order_and_drops_destroyed_windows() {
+    assert_eq!(reconcile_window_order(&[3, 1, 2], &[1, 2]), vec![1, 2]);
+  }
+
+  #[test]
+  fn reconcile_appends_new_windows() {
+    assert_eq!(reconcile_window_order(&[3, 1], &[1, 2, 3, 4]), vec![3, 1, 2, 4]);
+  }
+
+  #[test]
+  fn retile_that_moved_windows_takes_the_order_of_the_layout() {
+    assert_eq!(get_retiled_window_order(&[1, 2, 3], &[1, 3, 2], &[2, 1, 3]), vec![2, 1, 3]);
+  }
+
+  #[test]
+  fn retile_that_moved_nothing_keeps_the_saved_order() {
+    assert_eq!(get_retiled_window_order(&[2, 1, 3], &[1, 2, 3, 4], &[1, 2, 3, 4]), vec![2, 1, 3, 4]);
+  }
+
+  #[test]
+  fn closed_master_is_replaced_by_the_promoted_stack_window() {
+    let order = [1, 2, 3, 4, 5];
+    let history = [4, 2];
+    assert_eq!(get_order_without_master(&order, 0, 2, MasterPromotion::TopOfStack, &history), (vec![3, 2, 4, 5], 2));
+    assert_eq!(get_order_without_master(&order, 0, 2, MasterPromotion::BottomOfStack, &history), (vec![5, 2, 3, 4], 2));
+    assert_eq!(
+      get_order_without_master(&order, 1, 2, MasterPromotion::MostRecentlyFocused, &history),
+      (vec![1, 4, 3, 5], 2)
+    );
+  }
+
+  #[test]
+  fn closed_master_shrinks_the_master_count_without_promotion() {
+    assert_eq!(get_order_without_master(&[1, 2, 3], 0, 2, MasterPromotion::None, &[]), (vec![2, 3], 1));
+    assert_eq!(get_order_without_master(&[1, 2, 3], 0, 1, MasterPromotion::None, &[]), (vec![2, 3], 1));
+  }
+
+  #[test]
+  fn closed_master_without_stack_leaves_the_other_masters() {
+    assert_eq!(get_order_without_master(&[1, 2], 1, 2, MasterPromotion::BottomOfStack, &[]), (vec![1], 2));
+  }
+
+  #[test]
+  fn reconcile_without_saved_order_uses_current_windows() {
+    assert_eq!(reconcile_window_order(&[], &[5, 4]), vec![5, 4]);
+  }
+}
@@ -1,6 +1,30 @@
+use std::cmp::Ordering;
+
 use log::{debug, error, trace};
 
-use crate::{window_manager::WindowsManager, yabai::window::Window};
+use crate::{
+  window_manager::WindowsManager,
+  yabai::{config::MasterPosition, window::Window},
+};
+
+/// Orders windows from top to bottom, then from left to right.
+/// Stacked windows share the same frame, so they are ordered by their position in the stack.
+pub(crate) fn compare_position(w1: &Window, w2: &Window) -> Ordering {
+  if w1.frame.y == w2.frame.y {
+    w1.frame.x.total_cmp(&w2.frame.x).then(w1.stack_index.cmp(&w2.stack_index))
+  } else {
+    w1.frame.y.total_cmp(&w2.frame.y)
+  }
+}
+
+/// Orders stack windows column by column, starting with the column next to the master pane, then from top to bottom.
+pub(crate) fn compare_stack_position(master_position: &MasterPosition, w1: &Window, w2: &Window) -> Ordering {
+  let by_column = match master_position {
+    MasterPosition::Left => w1.frame.x.total_cmp(&w2.frame.x),
+    MasterPosition::Right => w2.frame.x.total_cmp(&w1.frame.x),
+  };
+  by_column.then(compare_position(w1, w2))
+}
 
 impl WindowsManager {
   pub(crate) fn get_bottom_window(&self, windows: Vec<Window>) -> Option<Window> {
@@ -12,7 +36,9 @@ impl WindowsManager {
     let mut bottom_window = windows.first().unwrap();
 
     for window in windows.iter() {
-      if window.frame.y > bottom_window.frame.y {
+      if window.frame.y > bottom_window.frame.y
+        || (window.frame.y == bottom_window.frame.y && window.stack_index > bottom_window.stack_index)
+      {
         bottom_window = window;
       }
     }
@@ -20,24 +46,6 @@ impl WindowsManager {
     Some(bottom_window.clone())
   }
 
-  pub(crate) fn is_top_window(&self, windows: Vec<Window>, focused_window: &Window) -> bool {
-    let top_window = self.get_top_window(windows);
-    if let Some(top_window) = top_window {
-      top_window.id == focused_window.id
-    } else {
-      false
-    }
-  }
-
-  pub(crate) fn is_bottom_window(&self, windows: Vec<Window>, focused_window: &Window) -> bool {
-    let bottom_window = self.get_bottom_window(windows);
-    if let Some(bottom_window) = bottom_window {
-      bottom_window.id == focused_window.id
-    } else {
-      false
-    }
-  }
-
   pub(crate) fn get_top_window(&self, windows: Vec<Window>) -> Option<Window> {
     if windows.is_empty() {
       debug!("No windows provided to find top window");
@@ -46,7 +54,9 @@ impl WindowsManager {
 
     let mut top_window = windows.first().unwrap();
     for window in windows.iter() {
-      if window.frame.y < top_window.frame.y {
+      if window.frame.y < top_window.frame.y
+        || (window.frame.y == top_window.frame.y && window.stack_index < top_window.stack_index)
+      {
         top_window = window;
       }
     }
@@ -137,3 +147,44 @@ impl WindowsManager {
     self.windows.iter().filter(|w| self.is_middle_window(w)).collect::<Vec<_>>()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn stacked_windows_are_ordered_by_stack_index() {
+    let frame = (960.0, 0.0, 960.0, 1080.0);
+    let mut windows = [
+      Window::new_for_test(3, frame, 3),
+      Window::new_for_test(4, (0.0, 0.0, 960.0, 1080.0), 0),
+      Window::new_for_test(1, frame, 1),
+      Window::new_for_test(2, frame, 2),
+    ];
+    windows.sort_by(compare_position);
+    assert_eq!(windows.iter().map(|window| window.id).collect::<Vec<_>>(), vec![4, 1, 2, 3]);
+  }
+
+  #[test]
+  fn stack_windows_are_ordered_column_by_column() {
+    let mut windows = [
+      Window::new_for_test(1, (960.0, 0.0, 480.0, 540.0), 0),
+      Window::new_for_test(2, (1440.0, 0.0, 480.0, 540.0), 0),
+      Window::new_for_test(3, (960.0, 540.0, 480.0, 540.0), 0),
+      Window::new_for_test(4, (1440.0, 540.0, 480.0, 540.0), 0),
+    ];
+    windows.sort_by(|w1, w2| compare_stack_position(&MasterPosition::Left, w1, w2));
+    assert_eq!(windows.iter().map(|window| window.id).collect::<Vec<_>>(), vec![1, 3, 2, 4]);
+    windows.sort_by(|w1, w2| compare_stack_position(&MasterPosition::Right, w1, w2));
+    assert_eq!(windows.iter().map(|window| window.id).collect::<Vec<_>>(), vec![2, 4, 1, 3]);
+  }
+
+  #[test]
+  fn windows_are_ordered_top_to_bottom_before_stack_index() {
+    let top = Window::new_for_test(1, (960.0, 0.0, 960.0, 540.0), 2);
+    let bottom = Window::new_for_test(2, (960.0, 540.0, 960.0, 540.0), 1);
+    assert_eq!(compare_position(&top, &bottom), Ordering::Less);
+  }
+}
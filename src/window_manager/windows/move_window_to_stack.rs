@@ -16,6 +16,16 @@ impl WindowsManager {
       Ok(())
     } else {
       info!("Moving window {window} to stack");
+      if self.is_deck_mode()? {
+        let stack_window = self.get_stack_windows().into_iter().find(|stack_window| stack_window.id != window.id);
+        if let Some(stack_window) = stack_window {
+          info!("Stacking window {window} on {stack_window}");
+          let message = YabaiMessage::window(&stack_window).stack(window)?;
+          return self.send_yabai_message(message);
+        }
+        debug!("No stack to put window {window} on, creating one");
+      }
+
       let config = get_config()?;
       let message = YabaiMessage::window(window).warp(config.master_position)?;
       self.send_yabai_message(message)?;
@@ -3,30 +3,27 @@ use log::{trace, warn};
 
 use crate::{
   dry_mode::is_dry_mode,
-  task::lock::is_locked,
+  task::lock::is_lock_held,
   window_manager::WindowsManager,
   yabai::command::{message::YabaiMessage, to_command::Runnable},
 };
 
 impl WindowsManager {
   pub(crate) fn send_yabai_message(&self, message: YabaiMessage) -> color_eyre::Result<()> {
-    match is_locked() {
-      Ok(false) => {
-        if message.is_write() && is_dry_mode() {
-          warn!("Skipping {} as dry mode is enabled", message.blue());
-        } else {
-          trace!("Running yabai command: {}", message.blue());
-          message.run()?;
-        }
-        Ok(())
-      },
-      Ok(true) => {
-        bail!("Lockfile is already owned by another process");
-      },
-      Err(e) => {
-        bail!("Could not check if lockfile exists: {}", e);
-      },
+    if !is_lock_held() {
+      bail!("Refusing to run {} without holding the lock", message.blue());
     }
+
+    if message.is_write() && is_dry_mode() {
+      warn!("Skipping {} as dry mode is enabled", message.blue());
+    } else {
+      trace!("Running yabai command: {}", message.blue());
+      message.run()?;
+      if message.is_window_insertion() {
+        self.has_inserted_windows.set(true);
+      }
+    }
+    Ok(())
   }
 }
 
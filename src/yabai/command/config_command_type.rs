@@ -5,6 +5,7 @@ use crate::yabai::command::{message::YabaiMessageBuilder, to_argument::ToArgumen
 #[derive(Debug, Clone)]
 pub enum YabaiConfigCommandType {
   LeftPadding,
+  TopPadding,
 }
 
 impl YabaiMessageBuilder<(), YabaiConfigCommandType> {
@@ -15,12 +16,21 @@ impl YabaiMessageBuilder<(), YabaiConfigCommandType> {
     let string = String::from_utf8(output.stdout)?;
     string.parse().with_context(|| format!("failed to parse left padding: {}", string))
   }
+
+  pub fn top_padding(&mut self) -> color_eyre::Result<f64> {
+    self.message = Some(YabaiConfigCommandType::TopPadding);
+    let message = self.build()?;
+    let output = message.run()?;
+    let string = String::from_utf8(output.stdout)?;
+    string.parse().with_context(|| format!("failed to parse top padding: {}", string))
+  }
 }
 
 impl ToArgument for YabaiConfigCommandType {
   fn to_argument(&self) -> String {
     match self {
       YabaiConfigCommandType::LeftPadding => "left_padding".to_string(),
+      YabaiConfigCommandType::TopPadding => "top_padding".to_string(),
     }
   }
 }
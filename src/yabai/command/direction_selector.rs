@@ -1,6 +1,9 @@
+use serde::{Deserialize, Serialize};
+
 use crate::yabai::{command::to_argument::ToArgument, config::MasterPosition};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 // DIR_SEL     := north | east | south | west
 pub enum YabaiDirectionSelector {
   North,
@@ -4,6 +4,7 @@ use crate::yabai::{
   command::{
     config_command_type::YabaiConfigCommandType, display_command_type::YabaiDisplayCommandType,
     display_selector::YabaiDisplaySelector, message_type::YabaiMessageType, query_command_type::YabaiQueryCommandType,
+    space_command_type::YabaiSpaceCommandType, space_selector::YabaiSpaceSelector,
     window_command_type::YabaiWindowCommandType, window_selector::YabaiWindowSelector,
   },
   config::get_config,
@@ -63,5 +64,26 @@ impl YabaiMessage {
     YabaiMessageBuilder { selector: Some(display.into()), ..Default::default() }
   }
 
+  pub fn current_space() -> YabaiMessageBuilder<YabaiSpaceSelector, YabaiSpaceCommandType> {
+    YabaiMessageBuilder::default()
+  }
+
+  pub fn space<T: Into<YabaiSpaceSelector>>(
+    space: T,
+  ) -> YabaiMessageBuilder<YabaiSpaceSelector, YabaiSpaceCommandType> {
+    YabaiMessageBuilder { selector: Some(space.into()), ..Default::default() }
+  }
+
   pub fn is_write(&self) -> bool { self.is_write }
+
+  /// Whether the message re-inserts a window in the tree, which uses up the insertion point set with `--insert`.
+  pub fn is_window_insertion(&self) -> bool {
+    matches!(
+      self.message,
+      YabaiMessageType::Window(
+        _,
+        YabaiWindowCommandType::Warp(_) | YabaiWindowCommandType::Stack(_) | YabaiWindowCommandType::Insert(_)
+      )
+    )
+  }
 }
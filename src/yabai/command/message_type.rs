@@ -2,7 +2,8 @@ use log::debug;
 
 use crate::yabai::command::{
   config_command_type::YabaiConfigCommandType, display_command_type::YabaiDisplayCommandType,
-  display_selector::YabaiDisplaySelector, query_command_type::YabaiQueryCommandType, to_argument::ToArgument,
+  display_selector::YabaiDisplaySelector, query_command_type::YabaiQueryCommandType,
+  space_command_type::YabaiSpaceCommandType, space_selector::YabaiSpaceSelector, to_argument::ToArgument,
   window_command_type::YabaiWindowCommandType, window_selector::YabaiWindowSelector,
 };
 
@@ -10,6 +11,7 @@ use crate::yabai::command::{
 pub enum YabaiMessageType {
   Window(Option<YabaiWindowSelector>, YabaiWindowCommandType),
   Display(Option<YabaiDisplaySelector>, YabaiDisplayCommandType),
+  Space(Option<YabaiSpaceSelector>, YabaiSpaceCommandType),
   Config(YabaiConfigCommandType),
   Query(YabaiQueryCommandType),
 }
@@ -25,6 +27,10 @@ impl ToArgument for YabaiMessageType {
         format!("display {} {}", display.to_argument(), selector.to_argument())
       },
       YabaiMessageType::Display(None, selector) => format!("display {}", selector.to_argument()),
+      YabaiMessageType::Space(Some(space), selector) => {
+        format!("space {} {}", space.to_argument(), selector.to_argument())
+      },
+      YabaiMessageType::Space(None, selector) => format!("space {}", selector.to_argument()),
       YabaiMessageType::Config(config) => format!("config {}", config.to_argument()),
       YabaiMessageType::Query(query) => format!("query {}", query.to_argument()),
     }
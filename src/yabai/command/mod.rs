@@ -5,6 +5,8 @@ pub mod display_selector;
 pub mod message;
 pub mod message_type;
 mod query_command_type;
+pub mod resize_selector;
+pub mod space_command_type;
 pub mod space_selector;
 pub mod stack_selector;
 pub mod to_argument;
@@ -0,0 +1,21 @@
+use crate::yabai::command::to_argument::ToArgument;
+
+// RESIZE_SEL  := top|left|bottom|right|top_left|top_right|bottom_right|bottom_left|abs :<dx>:<dy>
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum YabaiResizeSelector {
+  /// left:<dx>:<dy>
+  Left(i32, i32),
+  /// bottom:<dx>:<dy>
+  Bottom(i32, i32),
+  /// right:<dx>:<dy>
+  Right(i32, i32),
+}
+impl ToArgument for YabaiResizeSelector {
+  fn to_argument(&self) -> String {
+    match self {
+      YabaiResizeSelector::Left(dx, dy) => format!("left:{dx}:{dy}"),
+      YabaiResizeSelector::Bottom(dx, dy) => format!("bottom:{dx}:{dy}"),
+      YabaiResizeSelector::Right(dx, dy) => format!("right:{dx}:{dy}"),
+    }
+  }
+}
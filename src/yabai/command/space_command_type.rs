@@ -0,0 +1,56 @@
+use color_eyre::eyre::OptionExt;
+
+use crate::yabai::{
+  command::{
+    message::{YabaiMessage, YabaiMessageBuilder},
+    message_type::YabaiMessageType,
+    space_selector::YabaiSpaceSelector,
+    to_argument::ToArgument,
+  },
+  config::get_config,
+};
+
+#[derive(Debug, Clone)]
+pub enum YabaiSpaceCommandType {
+  /// Adjust the split ratios of the selected space so that all windows along each axis occupy the same area.
+  Balance,
+}
+
+impl YabaiMessageBuilder<YabaiSpaceSelector, YabaiSpaceCommandType> {
+  /// Build the YabaiMessage from the builder.
+  fn build(&self) -> color_eyre::Result<YabaiMessage> {
+    let command = get_config().map(|config| config.yabai_path).unwrap_or("yabai".to_string());
+    let message = self.message.as_ref().ok_or_eyre("no command set")?.clone();
+    Ok(YabaiMessage { command, message: YabaiMessageType::Space(self.selector.clone(), message), is_write: true })
+  }
+
+  pub fn balance(&mut self) -> color_eyre::Result<YabaiMessage> {
+    self.message = Some(YabaiSpaceCommandType::Balance);
+    self.build()
+  }
+}
+
+impl ToArgument for YabaiSpaceCommandType {
+  fn to_argument(&self) -> String {
+    match self {
+      YabaiSpaceCommandType::Balance => "--balance".into(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_balance() {
+    let message = YabaiMessage::current_space().balance().unwrap();
+    assert_eq!(message.message.to_argument(), "space --balance");
+  }
+
+  #[test]
+  fn test_balance_space() {
+    let message = YabaiMessage::space(2).balance().unwrap();
+    assert_eq!(message.message.to_argument(), "space 2 --balance");
+  }
+}
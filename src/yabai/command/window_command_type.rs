@@ -6,6 +6,7 @@ use crate::yabai::{
     display_selector::YabaiDisplaySelector,
     message::{YabaiMessage, YabaiMessageBuilder},
     message_type::YabaiMessageType,
+    resize_selector::YabaiResizeSelector,
     space_selector::YabaiSpaceSelector,
     to_argument::ToArgument,
     toggle_selector::YabaiToggleSelector,
@@ -43,6 +44,9 @@ pub enum YabaiWindowCommandType {
   /// Any kind of warp operation performed on a stacked window will unstack it.
   Stack(YabaiWindowSelector),
   Insert(YabaiDirectionSelector),
+  /// Resize the selected window by moving the given edge.
+  /// Resizing a tiled window changes the split ratio of the node it belongs to.
+  Resize(YabaiResizeSelector),
   // Grid()
   /// Toggle the given property of the selected window.
   /// The following properties requires System Integrity Protection to be partially disabled: sticky, pip, shadow, LABEL
@@ -108,6 +112,11 @@ impl YabaiMessageBuilder<YabaiWindowSelector, YabaiWindowCommandType> {
     self.build()
   }
 
+  pub fn resize<T: Into<YabaiResizeSelector>>(&mut self, selector: T) -> color_eyre::Result<YabaiMessage> {
+    self.message = Some(YabaiWindowCommandType::Resize(selector.into()));
+    self.build()
+  }
+
   pub fn toggle<T: Into<YabaiToggleSelector>>(&mut self, selector: T) -> color_eyre::Result<YabaiMessage> {
     self.message = Some(YabaiWindowCommandType::Toggle(selector.into()));
     self.build()
@@ -138,6 +147,7 @@ impl ToArgument for YabaiWindowCommandType {
       YabaiWindowCommandType::Insert(selector) => {
         format!("--insert {}", selector.to_argument())
       },
+      YabaiWindowCommandType::Resize(selector) => format!("--resize {}", selector.to_argument()),
       YabaiWindowCommandType::Toggle(selector) => {
         format!("--toggle {}", selector.to_argument())
       },
@@ -219,4 +229,10 @@ mod window_command_type_tests {
     let message = YabaiMessage::current_window().insert(YabaiDirectionSelector::North).unwrap();
     assert_eq!(message.message.to_argument(), "window --insert north");
   }
+
+  #[test_log::test]
+  fn resize_window_edge() {
+    let message = YabaiMessage::window(YabaiWindowSelector::Id(42)).resize(YabaiResizeSelector::Left(-20, 0)).unwrap();
+    assert_eq!(message.message.to_argument(), "window 42 --resize left:-20:0");
+  }
 }
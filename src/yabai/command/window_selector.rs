@@ -82,6 +82,12 @@ impl From<YabaiDirectionSelector> for YabaiWindowSelector {
 impl From<YabaiDirectionSelector> for Option<YabaiWindowSelector> {
   fn from(selector: YabaiDirectionSelector) -> Self { Some(YabaiWindowSelector::DirectionSelector(selector)) }
 }
+impl From<YabaiStackSelector> for YabaiWindowSelector {
+  fn from(selector: YabaiStackSelector) -> Self { YabaiWindowSelector::StackSelector(selector) }
+}
+impl From<YabaiStackSelector> for Option<YabaiWindowSelector> {
+  fn from(selector: YabaiStackSelector) -> Self { Some(YabaiWindowSelector::StackSelector(selector)) }
+}
 impl From<MasterPosition> for YabaiWindowSelector {
   fn from(value: MasterPosition) -> Self { YabaiWindowSelector::DirectionSelector(value.into()) }
 }
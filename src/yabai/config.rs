@@ -1,5 +1,5 @@
 use core::fmt::Display;
-use std::{fmt::Debug, path::PathBuf};
+use std::{collections::HashMap, fmt::Debug, path::PathBuf};
 
 use color_eyre::{
   eyre::{bail, eyre},
@@ -18,6 +18,61 @@ pub enum MasterPosition {
   Right,
 }
 
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StackMode {
+  /// Every stack window has its own row in the stack pane
+  #[default]
+  Split,
+  /// Every stack window is part of a single yabai stack
+  Deck,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CloseFocusPolicy {
+  /// Focus the window following the closed window
+  NextInStack,
+  /// Focus the first master window
+  Master,
+  /// Focus the most recently focused window
+  #[default]
+  LastFocused,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MasterPromotion {
+  /// The top stack window becomes master
+  TopOfStack,
+  /// The bottom stack window becomes master
+  #[default]
+  BottomOfStack,
+  /// The most recently focused stack window becomes master
+  MostRecentlyFocused,
+  /// No window becomes master, the number of master windows shrinks instead
+  None,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NewWindowPlacement {
+  /// New windows become the first master window
+  Master,
+  /// New windows go to the top of the stack
+  #[serde(alias = "stack_top")]
+  StackTop,
+  /// New windows go to the bottom of the stack
+  #[serde(alias = "stack_bottom")]
+  StackBottom,
+  /// New windows go right after the focused window
+  #[serde(alias = "after_focused")]
+  AfterFocused,
+  /// New windows go right before the focused window
+  #[serde(alias = "before_focused")]
+  BeforeFocused,
+}
+
 pub trait ToYabaiDirection {
   fn to_yabai_direction(&self) -> &str;
 }
@@ -49,6 +104,37 @@ pub struct YabaiMasterStackPluginConfig {
   pub(crate) debug: bool,
   pub(crate) move_new_windows_to_master: bool,
   pub(crate) master_position: MasterPosition,
+  pub(crate) master_ratio_step: f64,
+  pub(crate) stack_mode: StackMode,
+  pub(crate) max_stack_rows: Option<usize>,
+  pub(crate) close_focus_policy: CloseFocusPolicy,
+  pub(crate) master_promotion: MasterPromotion,
+  pub(crate) new_window_placement: Option<NewWindowPlacement>,
+  /// Placement of the new windows of an application, by application name
+  pub(crate) new_window_placement_overrides: HashMap<String, NewWindowPlacement>,
+  /// How long to wait for another ymsp process to release the lock, in milliseconds
+  pub(crate) lock_timeout_ms: u64,
+  /// How long to wait for more events of a kind before handling the last one, in milliseconds, by event name
+  pub(crate) event_debounce_ms: HashMap<String, u64>,
+}
+
+impl YabaiMasterStackPluginConfig {
+  /// Where a new window of `app` goes. `moveNewWindowsToMaster` is only used when no placement is configured.
+  pub(crate) fn get_new_window_placement(&self, app: &str) -> NewWindowPlacement {
+    match self.new_window_placement_overrides.get(app) {
+      Some(placement) => *placement,
+      None => self.get_default_new_window_placement(),
+    }
+  }
+
+  /// Where new windows go when their application has no override.
+  pub(crate) fn get_default_new_window_placement(&self) -> NewWindowPlacement {
+    match self.new_window_placement {
+      Some(placement) => placement,
+      None if self.move_new_windows_to_master => NewWindowPlacement::Master,
+      None => NewWindowPlacement::StackTop,
+    }
+  }
 }
 
 impl Display for YabaiMasterStackPluginConfig {
@@ -60,7 +146,7 @@ impl Display for YabaiMasterStackPluginConfig {
       self.yabai_path,
       self.yabai_path
     );
-    write!(f, "YabaiMasterStackPluginConfig {{ yabai_path: {}, debug: {}, move_new_windows_to_master: {}, master_position: {} }}", path, debug, move_new_windows_to_master, self.master_position)
+    write!(f, "YabaiMasterStackPluginConfig {{ yabai_path: {}, debug: {}, move_new_windows_to_master: {}, master_position: {}, master_ratio_step: {}, stack_mode: {}, max_stack_rows: {}, close_focus_policy: {}, master_promotion: {}, new_window_placement: {}, new_window_placement_overrides: {}, lock_timeout_ms: {}, event_debounce_ms: {} }}", path, debug, move_new_windows_to_master, self.master_position, self.master_ratio_step.blue(), format!("{:?}", self.stack_mode).yellow(), format!("{:?}", self.max_stack_rows).blue(), format!("{:?}", self.close_focus_policy).yellow(), format!("{:?}", self.master_promotion).yellow(), format!("{:?}", self.new_window_placement).yellow(), format!("{:?}", self.new_window_placement_overrides).yellow(), self.lock_timeout_ms.blue(), format!("{:?}", self.event_debounce_ms).yellow())
   }
 }
 
@@ -75,6 +161,16 @@ impl Default for YabaiMasterStackPluginConfig {
       debug: false,
       move_new_windows_to_master: false,
       master_position: Default::default(),
+      master_ratio_step: 0.05,
+      stack_mode: Default::default(),
+      max_stack_rows: None,
+      close_focus_policy: Default::default(),
+      master_promotion: Default::default(),
+      new_window_placement: None,
+      new_window_placement_overrides: HashMap::new(),
+      lock_timeout_ms: 5000,
+      // Dragging a window emits a burst of moves, re-tiling during the drag fights the user
+      event_debounce_ms: HashMap::from([("window_moved".to_string(), 150), ("window_resized".to_string(), 150)]),
     }
   }
 }
@@ -92,7 +188,7 @@ pub fn check_config_path_exists() -> color_eyre::Result<()> {
   if exists {
     Ok(())
   } else {
-    bail!("Configuration path {path:?} does not exist")
+    bail!("Configuration path {path:?} does not exist");
   }
 }
 
@@ -110,6 +206,28 @@ pub fn get_lockfile() -> color_eyre::Result<PathBuf> {
   Ok(lockfile)
 }
 
+pub fn get_event_queue_path() -> color_eyre::Result<PathBuf> {
+  let path = get_config_path()?;
+  let queue = path.join("events.jsonl");
+
+  Ok(queue)
+}
+
+pub fn get_socket_path() -> color_eyre::Result<PathBuf> {
+  let path = get_config_path()?;
+  let socket = path.join("ymsp.sock");
+
+  Ok(socket)
+}
+
+/// The marker coordinating the processes debouncing the events with `debounce_key`.
+pub fn get_debounce_marker_path(debounce_key: &str) -> color_eyre::Result<PathBuf> {
+  let path = get_config_path()?;
+  let marker = path.join(format!("debounce.{debounce_key}.marker"));
+
+  Ok(marker)
+}
+
 fn get_config_file() -> color_eyre::Result<PathBuf> {
   let path = get_config_path()?;
   let config_file_path = path.join("ymsp.config.json");
@@ -132,14 +250,14 @@ fn _initialize_config() -> color_eyre::Result<YabaiMasterStackPluginConfig> {
     trace!("Deserialized configuration: {data}");
     Ok(data)
   } else {
-    bail!("Configuration file {config_file_path:?} not found")
+    bail!("Configuration file {config_file_path:?} not found");
   }
 }
 
+/// Reads the configuration, once per process as the daemon runs many tasks.
 #[cfg(not(test))]
 pub fn initialize_config() -> color_eyre::Result<()> {
-  let config = _initialize_config()?;
-  CELL.set(config).map_err(|_| eyre!("Failed to set config"))?;
+  CELL.get_or_try_init(_initialize_config)?;
 
   Ok(())
 }
@@ -151,7 +269,7 @@ pub fn initialize_config() -> color_eyre::Result<()> { Ok(()) }
 pub fn get_config() -> color_eyre::Result<YabaiMasterStackPluginConfig> {
   match CELL.get() {
     Some(value) => Ok(value.to_owned()),
-    None => bail!("Config not set"),
+    None => Err(eyre!("Config not set")),
   }
 }
 
@@ -162,5 +280,47 @@ pub fn get_config() -> color_eyre::Result<YabaiMasterStackPluginConfig> {
     master_position: MasterPosition::Left,
     move_new_windows_to_master: false,
     yabai_path: "yabai".to_string(),
+    master_ratio_step: 0.05,
+    stack_mode: StackMode::Split,
+    max_stack_rows: None,
+    close_focus_policy: CloseFocusPolicy::LastFocused,
+    master_promotion: MasterPromotion::BottomOfStack,
+    new_window_placement: None,
+    new_window_placement_overrides: HashMap::new(),
+    lock_timeout_ms: 5000,
+    event_debounce_ms: HashMap::new(),
   })
 }
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn new_window_placement_falls_back_to_legacy_option() {
+    let config: YabaiMasterStackPluginConfig = serde_json::from_str(r#"{ "moveNewWindowsToMaster": true }"#).unwrap();
+    assert_eq!(config.get_new_window_placement("Safari"), NewWindowPlacement::Master);
+
+    let config: YabaiMasterStackPluginConfig = serde_json::from_str(r#"{ "moveNewWindowsToMaster": false }"#).unwrap();
+    assert_eq!(config.get_new_window_placement("Safari"), NewWindowPlacement::StackTop);
+  }
+
+  #[test]
+  fn new_window_placement_overrides_legacy_option() {
+    let config: YabaiMasterStackPluginConfig =
+      serde_json::from_str(r#"{ "moveNewWindowsToMaster": true, "newWindowPlacement": "stack_bottom" }"#).unwrap();
+    assert_eq!(config.get_new_window_placement("Safari"), NewWindowPlacement::StackBottom);
+  }
+
+  #[test]
+  fn new_window_placement_per_app() {
+    let config: YabaiMasterStackPluginConfig = serde_json::from_str(
+      r#"{ "newWindowPlacement": "afterFocused", "newWindowPlacementOverrides": { "Terminal": "master" } }"#,
+    )
+    .unwrap();
+    assert_eq!(config.get_new_window_placement("Terminal"), NewWindowPlacement::Master);
+    assert_eq!(config.get_new_window_placement("Safari"), NewWindowPlacement::AfterFocused);
+  }
+}
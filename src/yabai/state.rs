@@ -1,81 +1,542 @@
-use std::collections::HashMap;
+use std::{
+  collections::HashMap,
+  io::Write,
+  path::{Path, PathBuf},
+  sync::Mutex,
+  time::SystemTime,
+};
 
-use color_eyre::owo_colors::OwoColorize;
-use log::debug;
+use color_eyre::{
+  eyre::{bail, eyre},
+  owo_colors::OwoColorize,
+};
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 
-use crate::yabai::{command::message::YabaiMessage, config::get_state_path, spaces::Space};
+use crate::{
+  task::daemon::is_daemon,
+  window_manager::layouts::Layout,
+  yabai::{
+    command::{direction_selector::YabaiDirectionSelector, message::YabaiMessage},
+    config::get_state_path,
+    spaces::Space,
+    window::WindowId,
+  },
+};
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// Arrangement of a space saved when its focused window was zoomed by `toggle-monocle`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Monocle {
+  /// The zoomed window.
+  pub(crate) window: WindowId,
+  /// The number of master windows before the zoom.
+  pub(crate) master_count: usize,
+  /// The windows before the zoom, master windows first.
+  pub(crate) windows: Vec<WindowId>,
+}
+
+/// Where yabai inserts the next window created in a space.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsertionPoint {
+  /// The window split by the next window.
+  pub(crate) window: WindowId,
+  /// The side of the window where the next window goes.
+  pub(crate) direction: YabaiDirectionSelector,
+}
+
+/// Version of the state file written by this version of ymsp.
+const STATE_VERSION: u32 = 1;
+
+/// Number of windows remembered in the focus history of a space.
+const FOCUS_HISTORY_LEN: usize = 32;
+
+/// Everything ymsp remembers about a space.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpaceState {
+  /// The number of master windows.
+  pub(crate) master_count: usize,
+  /// The share of the display taken by the master pane, if it was set.
+  pub(crate) ratio: Option<f64>,
+  /// The layout used to tile the windows.
+  pub(crate) layout: Layout,
+  /// The windows, master windows first, then stack windows.
+  pub(crate) window_order: Vec<WindowId>,
+  /// Whether the space is paused. Only recorded for now, paused spaces are tiled like the others.
+  pub(crate) paused: bool,
+  /// The windows, the most recently focused first.
+  pub(crate) focus_history: Vec<WindowId>,
+  /// The arrangement saved by `toggle-monocle`.
+  pub(crate) monocle: Option<Monocle>,
+  /// The master window displaced by the last `zoom`.
+  pub(crate) zoom: Option<WindowId>,
+  /// The insertion point last set by ymsp.
+  pub(crate) insertion_point: Option<InsertionPoint>,
+}
+
+impl Default for SpaceState {
+  fn default() -> Self {
+    Self {
+      master_count: 1,
+      ratio: None,
+      layout: Default::default(),
+      window_order: vec![],
+      paused: false,
+      focus_history: vec![],
+      monocle: None,
+      zoom: None,
+      insertion_point: None,
+    }
+  }
+}
+
+/// The state last read or written by the daemon, with the modification time of the state file holding it.
+static STATE_CACHE: Mutex<Option<(SystemTime, State)>> = Mutex::new(None);
+
+fn get_modification_time(path: &Path) -> Option<SystemTime> { std::fs::metadata(path).ok()?.modified().ok() }
+
+/// The cached state, unless the state file was changed by another process since it was cached.
+fn get_cached_state(path: &Path) -> Option<State> {
+  let modification_time = get_modification_time(path)?;
+  let cache = STATE_CACHE.lock().ok()?;
+  cache.as_ref().filter(|(cached_time, _)| *cached_time == modification_time).map(|(_, state)| state.clone())
+}
+
+fn set_cached_state(path: &Path, state: &State) {
+  if let (Some(modification_time), Ok(mut cache)) = (get_modification_time(path), STATE_CACHE.lock()) {
+    *cache = Some((modification_time, state.clone()));
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
+  version: u32,
+  /// The spaces, by label, or by uuid for spaces without a label.
+  spaces: HashMap<String, SpaceState>,
+  /// Spaces of older state files, by space id, until they are matched with the current spaces.
+  #[serde(skip)]
+  legacy_spaces: HashMap<usize, SpaceState>,
+}
+
+impl Default for State {
+  fn default() -> Self { Self { version: STATE_VERSION, spaces: HashMap::new(), legacy_spaces: HashMap::new() } }
+}
+
+/// Key of a space in the state: space ids change when macOS restarts or displays are reconnected, labels and uuids
+/// don't.
+pub(crate) fn get_space_key(space: &Space) -> String {
+  if space.label.is_empty() {
+    space.uuid.to_string()
+  } else {
+    space.label.clone()
+  }
+}
+
+/// State files written before the state was versioned, with the master count of each space by space id.
+#[derive(Debug, Default, Deserialize)]
+struct LegacyState {
   values: HashMap<usize, usize>,
 }
 
+impl From<LegacyState> for State {
+  fn from(legacy: LegacyState) -> Self {
+    let legacy_spaces = legacy
+      .values
+      .into_iter()
+      .map(|(id, master_count)| (id, SpaceState { master_count, ..Default::default() }))
+      .collect();
+
+    Self { legacy_spaces, ..Default::default() }
+  }
+}
+
 pub trait StateForSpace {
   fn get_space(&self, space: &Space) -> color_eyre::Result<&usize>;
   fn get_space_mut(&mut self, space: &Space) -> color_eyre::Result<&mut usize>;
 }
 
 impl State {
+  /// Parse a state file, migrating it from the format of older versions of ymsp.
+  pub(crate) fn from_json(json: &str) -> color_eyre::Result<Self> {
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let version = value.get("version").and_then(serde_json::Value::as_u64);
+    match version {
+      Some(version) if version > u64::from(STATE_VERSION) => {
+        bail!("The state file was written by a newer version of ymsp (version {version}, expected {STATE_VERSION})");
+      },
+      Some(_) => Ok(serde_json::from_value(value)?),
+      None => {
+        debug!("Migrating unversioned state");
+        Ok(serde_json::from_value::<LegacyState>(value)?.into())
+      },
+    }
+  }
+
+  /// Move the spaces of an older state file to the keys of the current spaces with the same id.
+  pub(crate) fn match_legacy_spaces(&mut self, spaces: &[Space]) {
+    for (space_id, space_state) in std::mem::take(&mut self.legacy_spaces) {
+      match spaces.iter().find(|space| space.id == space_id) {
+        Some(space) => {
+          debug!("Migrating space {space_id} to {key}", key = get_space_key(space).yellow());
+          self.spaces.entry(get_space_key(space)).or_insert(space_state);
+        },
+        None => debug!("Space {space_id} of the old state doesn't exist anymore"),
+      }
+    }
+  }
+
+  /// The spaces of the state, by key.
+  pub(crate) fn get_spaces(&self) -> &HashMap<String, SpaceState> { &self.spaces }
+
+  /// Forget everything about the space.
+  pub(crate) fn reset_space(&mut self, space: &Space) {
+    self.spaces.insert(get_space_key(space), SpaceState::default());
+  }
+
+  /// Forget everything about every space.
+  pub(crate) fn reset(&mut self, spaces: &[Space]) {
+    self.spaces.clear();
+    for space in spaces {
+      self.reset_space(space);
+    }
+  }
+
+  /// Drop the spaces that are not in `spaces`, and return their keys.
+  pub(crate) fn gc(&mut self, spaces: &[Space]) -> Vec<String> {
+    let keys = spaces.iter().map(get_space_key).collect::<Vec<_>>();
+    let mut removed = self.spaces.keys().filter(|key| !keys.contains(key)).cloned().collect::<Vec<_>>();
+    removed.sort();
+    for key in &removed {
+      self.spaces.remove(key);
+    }
+
+    removed
+  }
+
+  fn get_space_state(&self, space: &Space) -> Option<&SpaceState> { self.spaces.get(&get_space_key(space)) }
+
+  fn get_space_state_mut(&mut self, space: &Space) -> &mut SpaceState {
+    self.spaces.entry(get_space_key(space)).or_default()
+  }
+
   pub fn get_space(&self, space: &Space) -> color_eyre::Result<&usize> {
-    use color_eyre::eyre::eyre;
-    self.values.get(&space.id).ok_or(eyre!("Unable to get the space {id}", id = space.id))
+    self
+      .get_space_state(space)
+      .map(|space_state| &space_state.master_count)
+      .ok_or(eyre!("Unable to get the space {id}", id = space.id))
   }
 
   pub fn get_space_mut(&mut self, space: &Space) -> color_eyre::Result<&mut usize> {
-    use color_eyre::eyre::eyre;
-    self.values.get_mut(&space.id).ok_or(eyre!("Unable to get the space {id}", id = space.id))
+    self
+      .spaces
+      .get_mut(&get_space_key(space))
+      .map(|space_state| &mut space_state.master_count)
+      .ok_or(eyre!("Unable to get the space {id}", id = space.id))
+  }
+
+  pub fn get_ratio(&self, space: &Space) -> Option<f64> { self.get_space_state(space)?.ratio }
+
+  pub fn set_ratio(&mut self, space: &Space, ratio: f64) { self.get_space_state_mut(space).ratio = Some(ratio); }
+
+  pub fn get_layout(&self, space: &Space) -> Layout {
+    self.get_space_state(space).map(|space_state| space_state.layout).unwrap_or_default()
+  }
+
+  pub fn set_layout(&mut self, space: &Space, layout: Layout) { self.get_space_state_mut(space).layout = layout; }
+
+  pub fn get_monocle(&self, space: &Space) -> Option<&Monocle> { self.get_space_state(space)?.monocle.as_ref() }
+
+  pub fn set_monocle(&mut self, space: &Space, monocle: Monocle) {
+    self.get_space_state_mut(space).monocle = Some(monocle);
+  }
+
+  pub fn take_monocle(&mut self, space: &Space) -> Option<Monocle> {
+    self.spaces.get_mut(&get_space_key(space))?.monocle.take()
+  }
+
+  /// The windows of the space, master windows first, then stack windows.
+  pub fn get_window_order(&self, space: &Space) -> Vec<WindowId> {
+    self.get_space_state(space).map(|space_state| space_state.window_order.clone()).unwrap_or_default()
+  }
+
+  pub fn set_window_order(&mut self, space: &Space, window_order: Vec<WindowId>) {
+    self.get_space_state_mut(space).window_order = window_order;
+  }
+
+  /// The master window displaced by the last `zoom` in the space.
+  pub fn get_zoom(&self, space: &Space) -> Option<WindowId> { self.get_space_state(space)?.zoom }
+
+  pub fn set_zoom(&mut self, space: &Space, window: WindowId) { self.get_space_state_mut(space).zoom = Some(window); }
+
+  /// The windows of the space, the most recently focused first.
+  pub fn get_focus_history(&self, space: &Space) -> &[WindowId] {
+    self.get_space_state(space).map(|space_state| space_state.focus_history.as_slice()).unwrap_or_default()
+  }
+
+  /// Move `window` to the front of the focus history of the space.
+  pub fn push_focus(&mut self, space: &Space, window: WindowId) {
+    let history = &mut self.get_space_state_mut(space).focus_history;
+    history.retain(|id| *id != window);
+    history.insert(0, window);
+    history.truncate(FOCUS_HISTORY_LEN);
+  }
+
+  /// Forget `window` in the focus history of the space.
+  pub fn remove_focus(&mut self, space: &Space, window: WindowId) {
+    if let Some(space_state) = self.spaces.get_mut(&get_space_key(space)) {
+      space_state.focus_history.retain(|id| *id != window);
+    }
+  }
+
+  /// The insertion point last set by ymsp in the space.
+  pub fn get_insertion_point(&self, space: &Space) -> Option<InsertionPoint> {
+    self.get_space_state(space)?.insertion_point.clone()
+  }
+
+  pub fn set_insertion_point(&mut self, space: &Space, insertion_point: Option<InsertionPoint>) {
+    self.get_space_state_mut(space).insertion_point = insertion_point;
   }
 
   pub fn write_state(&self) -> color_eyre::Result<()> {
     let state_file_path = get_state_path()?;
-    debug!("Writing state to {state_file_path:?}", state_file_path = state_file_path.yellow());
-    let file = std::fs::File::create(&state_file_path)?;
-    serde_json::to_writer(file, self)?;
-    debug!("State written to {state_file_path:?}", state_file_path = state_file_path.yellow());
+    self.write_state_to(&state_file_path)?;
+    if is_daemon() {
+      set_cached_state(&state_file_path, self);
+    }
 
     Ok(())
   }
 
+  /// Write the state to `path` atomically, keeping the previous state file as a backup if it was valid.
+  pub(crate) fn write_state_to(&self, path: &Path) -> color_eyre::Result<()> {
+    debug!("Writing state to {path:?}", path = path.yellow());
+    let json = serde_json::to_string(self)?;
+    // Each process writes its own temporary file, so racing writers never interleave
+    let temp_path = path.with_extension(format!("json.{pid}.tmp", pid = std::process::id()));
+    {
+      let mut file = std::fs::File::create(&temp_path)?;
+      file.write_all(json.as_bytes())?;
+      file.sync_all()?;
+    }
+
+    // A corrupted state file must not replace a good backup
+    let is_valid = std::fs::read_to_string(path).is_ok_and(|json| State::from_json(&json).is_ok());
+    if is_valid {
+      std::fs::copy(path, get_backup_path(path))?;
+    }
+    std::fs::rename(&temp_path, path)?;
+    debug!("State written to {path:?}", path = path.yellow());
+
+    Ok(())
+  }
+
+  /// Read the state from `path`, or from its backup if it is corrupted.
+  ///
+  /// Returns `None` when neither file holds a valid state, so it gets rebuilt.
+  pub(crate) fn read_state_from(path: &Path) -> color_eyre::Result<Option<Self>> {
+    for path in [path.to_path_buf(), get_backup_path(path)] {
+      if !path.try_exists()? {
+        debug!("No state file at {path:?}");
+        continue;
+      }
+
+      debug!("Reading state from {path:?}");
+      let json = std::fs::read_to_string(&path)?;
+      match State::from_json(&json) {
+        Ok(state) => return Ok(Some(state)),
+        Err(err) if get_version(&json).is_some_and(|version| version > u64::from(STATE_VERSION)) => return Err(err),
+        Err(err) => warn!("Ignoring corrupted state file {path:?}: {err}"),
+      }
+    }
+
+    Ok(None)
+  }
+
   pub fn read_state() -> color_eyre::Result<Self> {
     debug!("Reading base state");
     let state_file_path = get_state_path()?;
     debug!("Looking for state file at: {:?}", state_file_path.yellow());
     #[cfg(debug_assertions)]
     {
-      use log::warn;
       warn!("Removing state file for testing purposes");
       std::fs::remove_file(&state_file_path)?;
     }
-    let exists = state_file_path.try_exists()?;
-    if exists {
-      debug!("Reading state from {:?}", state_file_path);
-      let file = std::fs::File::open(state_file_path)?;
-      let mut state: State = serde_json::from_reader(file)?;
-      debug!("Filling spaces in the state");
-      let spaces = YabaiMessage::query().spaces()?;
-      for space in &spaces {
-        state.values.entry(space.id).or_insert(1);
-      }
 
-      for (space_id, _) in spaces.iter().enumerate() {
-        if !spaces.iter().any(|space| space.id == space_id) {
-          state.values.remove(&space_id);
+    let spaces = YabaiMessage::query().spaces()?;
+    let state = match is_daemon().then(|| get_cached_state(&state_file_path)).flatten() {
+      Some(state) => {
+        debug!("Using the cached state");
+        Some(state)
+      },
+      None => State::read_state_from(&state_file_path)?,
+    };
+    match state {
+      Some(mut state) => {
+        debug!("Filling spaces in the state");
+        state.match_legacy_spaces(&spaces);
+        // Spaces that are gone are kept, they come back with their display
+        for space in &spaces {
+          state.spaces.entry(get_space_key(space)).or_default();
         }
-      }
-      debug!("State: {state:?}");
-      Ok(state)
-    } else {
-      debug!("Creating new state");
-      let mut state = State::default();
-      let spaces = YabaiMessage::query().spaces()?;
-      for space in &spaces {
-        debug!("Adding space {space_id} to the state", space_id = space.id.blue());
-        state.values.entry(space.id).or_insert(1);
-      }
-      state.write_state()?;
-      debug!("New state: {state:?}");
-      Ok(state)
+        debug!("State: {state:?}");
+        Ok(state)
+      },
+      None => {
+        warn!("No valid state file, rebuilding the state from yabai");
+        let mut state = State::default();
+        for space in &spaces {
+          debug!("Adding space {space_id} to the state", space_id = space.id.blue());
+          state.spaces.entry(get_space_key(space)).or_default();
+        }
+        state.write_state()?;
+        debug!("New state: {state:?}");
+        Ok(state)
+      },
     }
   }
 }
+
+/// Version of a state file, if it has one.
+fn get_version(json: &str) -> Option<u64> {
+  let value: serde_json::Value = serde_json::from_str(json).ok()?;
+  value.get("version")?.as_u64()
+}
+
+fn get_backup_path(path: &Path) -> PathBuf { path.with_extension("json.bak") }
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::test_dir::TestDir;
+
+  #[test_log::test]
+  fn focus_history_keeps_most_recent_first() {
+    let space = space(3, "code");
+    let mut state = State::default();
+    state.push_focus(&space, 1);
+    state.push_focus(&space, 2);
+    state.push_focus(&space, 1);
+    assert_eq!(state.get_focus_history(&space), &[1, 2]);
+
+    state.remove_focus(&space, 1);
+    assert_eq!(state.get_focus_history(&space), &[2]);
+  }
+
+  fn space(id: usize, label: &str) -> Space {
+    Space { id, label: label.to_string(), uuid: uuid::Uuid::from_u128(id as u128), ..Default::default() }
+  }
+
+  #[test_log::test]
+  fn space_key_is_label_or_uuid() {
+    assert_eq!(get_space_key(&space(3, "code")), "code");
+    assert_eq!(get_space_key(&space(3, "")), "00000000-0000-0000-0000-000000000003");
+  }
+
+  #[test_log::test]
+  fn migrates_unversioned_state() {
+    let mut state = State::from_json(r#"{ "values": { "3": 2 } }"#).unwrap();
+    let space = space(3, "");
+    state.match_legacy_spaces(std::slice::from_ref(&space));
+    assert_eq!(state.version, STATE_VERSION);
+    assert_eq!(state.get_space(&space).unwrap(), &2);
+    assert_eq!(state.get_ratio(&space), None);
+    assert_eq!(state.get_layout(&space), Layout::default());
+  }
+
+  #[test_log::test]
+  fn migrates_space_ids_to_current_spaces() {
+    let mut state = State::from_json(r#"{ "values": { "3": 2, "4": 3 } }"#).unwrap();
+    state.match_legacy_spaces(&[space(7, "code"), space(3, "web")]);
+    assert_eq!(state.get_space(&space(3, "web")).unwrap(), &2);
+    assert_eq!(state.get_space(&space(4, "code")).ok(), None);
+  }
+
+  #[test_log::test]
+  fn reads_current_state() {
+    let mut state = State::default();
+    let space = space(3, "code");
+    state.set_layout(&space, Layout::Grid);
+    let state = State::from_json(&serde_json::to_string(&state).unwrap()).unwrap();
+    assert_eq!(state.get_space(&space).unwrap(), &1);
+    assert_eq!(state.get_layout(&space), Layout::Grid);
+  }
+
+  fn state_with_layout(layout: Layout) -> State {
+    let mut state = State::default();
+    state.set_layout(&space(3, "code"), layout);
+    state
+  }
+
+  #[test_log::test]
+  fn write_keeps_previous_state_as_backup() {
+    let dir = TestDir::new("state-backup");
+    state_with_layout(Layout::Wide).write_state_to(&dir.path("state.json")).unwrap();
+    state_with_layout(Layout::Grid).write_state_to(&dir.path("state.json")).unwrap();
+
+    let backup = State::read_state_from(&get_backup_path(&dir.path("state.json"))).unwrap().unwrap();
+    assert_eq!(backup.get_layout(&space(3, "code")), Layout::Wide);
+    let state = State::read_state_from(&dir.path("state.json")).unwrap().unwrap();
+    assert_eq!(state.get_layout(&space(3, "code")), Layout::Grid);
+  }
+
+  #[test_log::test]
+  fn truncated_state_is_recovered_from_backup() {
+    let dir = TestDir::new("state-truncated");
+    state_with_layout(Layout::Wide).write_state_to(&dir.path("state.json")).unwrap();
+    state_with_layout(Layout::Grid).write_state_to(&dir.path("state.json")).unwrap();
+    let json = std::fs::read_to_string(dir.path("state.json")).unwrap();
+    std::fs::write(dir.path("state.json"), &json[..json.len() / 2]).unwrap();
+
+    let state = State::read_state_from(&dir.path("state.json")).unwrap().unwrap();
+    assert_eq!(state.get_layout(&space(3, "code")), Layout::Wide);
+  }
+
+  #[test_log::test]
+  fn garbage_state_without_backup_is_rebuilt() {
+    let dir = TestDir::new("state-garbage");
+    std::fs::write(dir.path("state.json"), "not json at all").unwrap();
+
+    assert!(State::read_state_from(&dir.path("state.json")).unwrap().is_none());
+  }
+
+  #[test_log::test]
+  fn corrupted_state_does_not_replace_backup() {
+    let dir = TestDir::new("state-corrupted");
+    state_with_layout(Layout::Wide).write_state_to(&dir.path("state.json")).unwrap();
+    state_with_layout(Layout::Grid).write_state_to(&dir.path("state.json")).unwrap();
+    std::fs::write(dir.path("state.json"), "{").unwrap();
+    state_with_layout(Layout::Monocle).write_state_to(&dir.path("state.json")).unwrap();
+
+    let backup = State::read_state_from(&get_backup_path(&dir.path("state.json"))).unwrap().unwrap();
+    assert_eq!(backup.get_layout(&space(3, "code")), Layout::Wide);
+  }
+
+  #[test_log::test]
+  fn reading_newer_version_fails() {
+    let dir = TestDir::new("state-newer");
+    let json = r#"{ "version": 99, "spaces": {} }"#;
+    std::fs::write(dir.path("state.json"), json).unwrap();
+
+    assert!(State::read_state_from(&dir.path("state.json")).is_err());
+    assert_eq!(std::fs::read_to_string(dir.path("state.json")).unwrap(), json);
+    assert!(!get_backup_path(&dir.path("state.json")).exists());
+  }
+
+  #[test_log::test]
+  fn gc_drops_spaces_that_are_gone() {
+    let mut state = State::default();
+    state.reset(&[space(1, "code"), space(2, "web"), space(3, "")]);
+    let removed = state.gc(&[space(8, "web")]);
+
+    assert_eq!(removed, vec!["00000000-0000-0000-0000-000000000003".to_string(), "code".to_string()]);
+    assert_eq!(state.get_spaces().keys().collect::<Vec<_>>(), vec!["web"]);
+  }
+
+  #[test_log::test]
+  fn rejects_state_from_newer_version() {
+    let error = State::from_json(r#"{ "version": 99, "spaces": {} }"#).unwrap_err();
+    assert!(error.to_string().contains("newer version"));
+  }
+}
@@ -53,3 +53,43 @@ pub struct Window {
 impl Display for Window {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "{} [{}]", self.app.blue(), self.id.yellow()) }
 }
+
+#[cfg(test)]
+impl Window {
+  /// A tiled window of `(w, h)` at `(x, y)`, in slot `stack_index` of its yabai stack, or 0 when not stacked.
+  pub(crate) fn new_for_test(id: WindowId, (x, y, w, h): (f64, f64, f64, f64), stack_index: usize) -> Self {
+    Window {
+      id,
+      pid: 1,
+      app: "Terminal".to_string(),
+      title: format!("Window {id}"),
+      frame: Frame { x, y, w, h },
+      role: "AXWindow".to_string(),
+      subrole: "AXStandardWindow".to_string(),
+      display: 1,
+      space: 1,
+      level: 0,
+      sub_level: 0,
+      layer: "normal".to_string(),
+      sub_layer: "normal".to_string(),
+      opacity: 1.0,
+      split_type: SplitType::Vertical,
+      split_child: "first_child".to_string(),
+      stack_index,
+      can_move: true,
+      can_resize: true,
+      has_focus: false,
+      has_shadow: true,
+      has_parent_zoom: false,
+      has_fullscreen_zoom: false,
+      has_ax_reference: true,
+      is_native_fullscreen: false,
+      is_visible: true,
+      is_minimized: false,
+      is_hidden: false,
+      is_floating: false,
+      is_sticky: false,
+      is_grabbed: false,
+    }
+  }
+}